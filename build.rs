@@ -1,13 +1,57 @@
 use std::env;
 use std::fs;
 use std::path::Path;
+use std::process::Command;
 
 fn main() {
     let out_dir = env::var_os("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("target.rs");
     fs::write(
         &dest_path,
-        format!("const TARGET: &str = \"{}\";", env::var("TARGET").unwrap()),
+        format!(
+            "pub const TARGET: &str = \"{}\";",
+            env::var("TARGET").unwrap()
+        ),
     )
     .unwrap();
+
+    // commit is not available if built from a crate package,
+    // and git must not find another repository enclosing the package
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").unwrap();
+    let git_dir = Path::new(&manifest_dir).join(".git");
+    let commit = if git_dir.exists() {
+        Command::new("git")
+            .args(["rev-parse", "--short=9", "HEAD"])
+            .current_dir(&manifest_dir)
+            .output()
+            .ok()
+            .filter(|x| x.status.success())
+            .and_then(|x| String::from_utf8(x.stdout).ok())
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+    } else {
+        None
+    };
+    let dest_path = Path::new(&out_dir).join("commit.rs");
+    fs::write(
+        &dest_path,
+        format!("pub const COMMIT: Option<&str> = {commit:?};"),
+    )
+    .unwrap();
+
+    // rerun only when the commit is changed, instead of any change of the package
+    if git_dir.is_dir() {
+        println!("cargo:rerun-if-changed=build.rs");
+        let head = git_dir.join("HEAD");
+        println!("cargo:rerun-if-changed={}", head.to_string_lossy());
+        if let Some(x) = fs::read_to_string(&head)
+            .ok()
+            .and_then(|x| x.strip_prefix("ref: ").map(|x| x.trim().to_string()))
+        {
+            let path = git_dir.join(x);
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.to_string_lossy());
+            }
+        }
+    }
 }
@@ -1,32 +1,176 @@
+use crate::config::Config;
 use crate::exec::exec;
-use crate::toolchain::ToolChain;
-use anyhow::{anyhow, bail, Result};
+use crate::state::{now, State};
+use crate::toolchain::{tools, ToolChain, TOOLS};
+use crate::utils::*;
+use anyhow::{bail, Result};
+use log::{debug, info, LevelFilter};
+use semver::Version;
 use std::env;
 use std::process::Command;
+use std::time::Duration;
 
 pub async fn main(arg0: &str) -> Result<()> {
+    // proxy mode has no flags, so verbose output is enabled by env var
+    let verbose = env::var("VERYLUP_VERBOSE").is_ok_and(|x| !x.is_empty() && x != "0");
+    let level = if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+    init_logger(level)?;
+    check_pinned_verylup_version();
+
+    // discovering tools from all toolchains is deferred until it is necessary
+    // because proxy mode is invoked on every execution of tools
+    let known = TOOLS.contains(&arg0) || Config::load().tools.iter().any(|x| x == arg0);
+
     let arg1 = env::args().nth(1);
     let toolchain = arg1
         .as_ref()
         .filter(|x| x.starts_with('+'))
-        .map(|x| ToolChain::try_from(&x[1..]))
+        .map(|x| ToolChain::parse(&x[1..]))
         .transpose()?;
 
     let cmd_args: Vec<_> = env::args_os()
         .skip(1 + toolchain.is_some() as usize)
         .collect();
 
-    let default_toolchain =
-        ToolChain::default_toolchain().ok_or(anyhow!("no toolchain is found"))?;
+    let explicit = toolchain.is_some();
+    let default_toolchain = ToolChain::get_default_toolchain()?;
 
     let toolchain = toolchain.unwrap_or(default_toolchain);
+    let overridden = !explicit && ToolChain::override_toolchain().as_ref() == Some(&toolchain);
+
+    // the toolchain may be installed under another name
+    let toolchain = toolchain.resolve_installed().await;
+
     if !toolchain.exists() {
         bail!("toolchain \"{toolchain}\" is not found");
     }
 
-    let mut cmd = Command::new(toolchain.get_path(arg0));
-    cmd.args(cmd_args);
+    if !known && !toolchain.get_path(&exe_name(arg0)).exists() {
+        bail!(
+            "unknown shim name \"{arg0}\", verylup can be invoked as verylup or {}",
+            tools().join(", ")
+        );
+    }
+
+    update_reminder(&toolchain);
+    if overridden {
+        downgrade_note(&toolchain);
+    }
+
+    let path = toolchain.get_path(arg0);
+    debug!(
+        "proxying: {arg0} -> {} (toolchain: {toolchain})",
+        path.to_string_lossy()
+    );
+
+    // Tell the child process that it was invoked through verylup
+    let mut cmd = Command::new(path);
+    cmd.args(cmd_args)
+        .env("VERYLUP_SHIM", arg0)
+        .env("VERYLUP_TOOLCHAIN", toolchain.to_string());
     exec(&mut cmd)?;
 
     Ok(())
 }
+
+/// Note that the toolchain selected by a directory override is older than the newest installed one
+///
+/// No toolchain is executed to keep the startup fast, so the version of `latest` is the one
+/// recorded when it was installed.
+fn downgrade_note(toolchain: &ToolChain) {
+    if !Config::load().downgrade_note {
+        return;
+    }
+
+    let latest = ToolChain::Latest.get_install_info().and_then(|x| x.version);
+    let version = |x: &ToolChain| match x {
+        ToolChain::Version(x) => Some(x.clone()),
+        ToolChain::Latest => latest.clone(),
+        ToolChain::Local => None,
+    };
+
+    let Some(actual) = version(toolchain) else {
+        return;
+    };
+
+    let newest = ToolChain::list()
+        .iter()
+        .filter(|x| *x != toolchain)
+        .filter_map(version)
+        .max();
+
+    if let Some(newest) = newest.filter(|x| *x > actual) {
+        info!(
+            "override: {toolchain} ({actual}) is older than the newest installed {newest}, run `verylup config set downgrade_note false` to hide this note"
+        );
+    }
+}
+
+/// Remind that a newer toolchain is available.
+///
+/// This refers the latest version cached by the previous lookup of releases only,
+/// and the check is done at most once per `update_reminder_interval`.
+fn update_reminder(toolchain: &ToolChain) {
+    if *toolchain != ToolChain::Latest {
+        return;
+    }
+
+    let config = Config::load();
+    if !config.update_reminder {
+        return;
+    }
+
+    let mut state = State::load();
+    let interval = Duration::from_secs(config.update_reminder_interval * 60 * 60);
+    if !state.reminder_due(interval) {
+        return;
+    }
+
+    if let Ok(actual) = toolchain.get_actual_version() {
+        if let Some(latest) = available_update(&state, &actual) {
+            info!("update available: {latest} (current: {actual}), run `verylup update`");
+        }
+    }
+
+    state.last_reminder = Some(now());
+    let _ = state.save();
+}
+
+/// The cached latest version if it is newer than `actual`
+fn available_update<'a>(state: &'a State, actual: &Version) -> Option<&'a Version> {
+    state.latest_version.as_ref().filter(|x| *x > actual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(latest: Option<&str>) -> State {
+        State {
+            latest_version: latest.map(|x| Version::parse(x).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_available_if_cache_is_newer() {
+        let actual = Version::parse("0.16.0").unwrap();
+        let state = state(Some("0.16.1"));
+        assert_eq!(
+            available_update(&state, &actual),
+            Some(&Version::parse("0.16.1").unwrap())
+        );
+    }
+
+    #[test]
+    fn update_not_available_if_cache_is_not_newer() {
+        let actual = Version::parse("0.16.1").unwrap();
+        assert_eq!(available_update(&state(Some("0.16.1")), &actual), None);
+        assert_eq!(available_update(&state(Some("0.16.0")), &actual), None);
+        assert_eq!(available_update(&state(None), &actual), None);
+    }
+}
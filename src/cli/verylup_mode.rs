@@ -1,18 +1,23 @@
 use crate::config::Config;
-use crate::exec::exec;
-use crate::toolchain::{ToolChain, TOOLS};
+use crate::lock::Lock;
+use crate::manifest::Manifest;
+use crate::state::{format_elapsed, State};
+use crate::toolchain::{
+    download_archive, get_latest_toolchain_version, local_download_error, tools, InstallOption,
+    ToolChain, TOOLS,
+};
 use crate::utils::*;
 use anyhow::{anyhow, bail, Result};
 use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::aot::Shell;
-use console::Style;
-use fern::Dispatch;
-use log::{info, Level, LevelFilter};
+use log::{debug, info, warn, LevelFilter};
 use semver::Version;
 use std::env;
-use std::fs;
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -26,10 +31,28 @@ struct Opt {
     #[arg(long, global = true)]
     pub verbose: bool,
 
+    /// Confirm all prompts automatically (or set VERYLUP_YES)
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// Use an alternate configuration file
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Format of error messages (JSON also emits progress events to stdout)
+    #[arg(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    pub message_format: MessageFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     Show(OptShow),
@@ -41,28 +64,131 @@ enum Commands {
     Setup(OptSetup),
     Completion(OptCompletion),
     Config(OptConfig),
+    ReleaseInfo(OptReleaseInfo),
+    Component(OptComponent),
+    Lock(OptLock),
+    Status(OptStatus),
+    Relink(OptRelink),
+    Ensure(OptEnsure),
+    ListAvailable(OptListAvailable),
+    #[command(name = "self")]
+    SelfCommand(OptSelf),
+    Which(OptWhich),
+    Url(OptUrl),
+    Gc(OptGc),
+    Diff(OptDiff),
 }
 
 /// Show installed toolchains
 #[derive(Args)]
-pub struct OptShow {}
+pub struct OptShow {
+    /// Print the state of verylup and toolchains as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Print the directory of each toolchain per line
+    #[arg(long, conflicts_with = "json")]
+    paths: bool,
+
+    /// Separate paths by NUL instead of newline
+    #[arg(long, requires = "paths")]
+    print0: bool,
+
+    /// Show available versions on the release server together
+    #[arg(long, conflicts_with_all = ["json", "paths"])]
+    remote: bool,
+
+    /// Check that `veryl` of each toolchain can be executed
+    #[arg(long, conflicts_with_all = ["paths", "remote"])]
+    check_binaries: bool,
+
+    /// Print `name<TAB>version<TAB>flags` per toolchain in the stable format for scripts
+    #[arg(long, conflicts_with_all = ["json", "paths", "remote", "check_binaries"])]
+    porcelain: bool,
+}
 
 /// Update Veryl toolchains and verylup
 #[derive(Args)]
 pub struct OptUpdate {
-    /// Toolchain package path for offline installation
+    /// Toolchain package path, or directory of packages, for offline installation
     #[arg(long)]
     pkg: Option<PathBuf>,
+
+    /// Make the installed toolchain read-only for shared installation
+    #[arg(long)]
+    system: bool,
+
+    /// Keep the downloaded archive in a given directory
+    #[arg(long, value_name = "DIR")]
+    keep_archive: Option<PathBuf>,
+
+    /// Skip checksum verification of downloaded archives
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Print what would be updated without changing anything
+    #[arg(long)]
+    dry_run: bool,
 }
 
 /// Install or update a given toolchain
 #[derive(Args)]
+#[command(after_help = INSTALL_EXAMPLES)]
 pub struct OptInstall {
-    target: String,
+    #[arg(required_unless_present = "manifest")]
+    target: Option<String>,
 
-    /// Toolchain package path for offline installation
-    #[arg(long)]
+    /// Toolchain package path, or directory of packages, for offline installation
+    #[arg(long, conflicts_with = "manifest")]
     pkg: Option<PathBuf>,
+
+    /// Install toolchains listed in a manifest generated by `verylup lock`
+    #[arg(long, conflicts_with = "target")]
+    manifest: Option<PathBuf>,
+
+    /// Make the installed toolchain read-only for shared installation
+    #[arg(long)]
+    system: bool,
+
+    /// Set the installed toolchain as default
+    #[arg(long)]
+    set_default: bool,
+
+    /// Install "local" toolchain from the binaries already built by `cargo build`
+    #[arg(long)]
+    no_build: bool,
+
+    /// Keep the downloaded archive in a given directory
+    #[arg(long, value_name = "DIR")]
+    keep_archive: Option<PathBuf>,
+
+    /// Skip checksum verification of downloaded archives
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Skip signature verification of downloaded archives
+    #[arg(long)]
+    no_verify_signature: bool,
+
+    /// Trust this version of the package instead of executing it (e.g. for another platform)
+    #[arg(long, value_name = "VERSION", requires = "pkg")]
+    assume_version: Option<Version>,
+
+    /// Check the installed toolchain can be executed on this host
+    #[arg(long, conflicts_with = "extract_only")]
+    smoke_test: bool,
+
+    /// Directory to extract the toolchain into by "--extract-only"
+    #[arg(long, requires = "extract_only")]
+    output_dir: Option<PathBuf>,
+
+    /// Only extract the toolchain into "--output-dir" without registering it
+    #[arg(
+        long,
+        requires = "output_dir",
+        conflicts_with_all = ["manifest", "system", "set_default", "no_build"]
+    )]
+    extract_only: bool,
 }
 
 /// Uninstall a given toolchain
@@ -74,11 +200,17 @@ pub struct OptUninstall {
 /// Set a given toolchain as default
 #[derive(Args)]
 pub struct OptDefault {
-    target: String,
+    #[arg(required_unless_present = "unset")]
+    target: Option<String>,
+
+    /// Remove the configured default toolchain
+    #[arg(long, conflicts_with = "target")]
+    unset: bool,
 }
 
 /// Modify toolchain overrides for directories
 #[derive(Args)]
+#[command(after_help = OVERRIDE_EXAMPLES)]
 pub struct OptOverride {
     #[command(subcommand)]
     command: OverrideCommand,
@@ -97,6 +229,7 @@ pub struct OptOverrideList {}
 
 /// Set the override toolchain for a directory
 #[derive(Args)]
+#[command(after_help = OVERRIDE_EXAMPLES)]
 pub struct OptOverrideSet {
     target: String,
 }
@@ -112,7 +245,7 @@ pub struct OptSetup {
     #[arg(long)]
     offline: bool,
 
-    /// Toolchain package path for offline installation
+    /// Toolchain package path, or directory of packages, for offline installation
     #[arg(long)]
     pkg: Option<PathBuf>,
 }
@@ -136,6 +269,7 @@ pub enum CompletionShell {
 
 /// Modify verylup configuration
 #[derive(Args)]
+#[command(after_help = CONFIG_EXAMPLES)]
 pub struct OptConfig {
     #[command(subcommand)]
     command: ConfigCommand,
@@ -144,20 +278,245 @@ pub struct OptConfig {
 #[derive(Subcommand)]
 pub enum ConfigCommand {
     Show(OptConfigShow),
+    Get(OptConfigGet),
     Set(OptConfigSet),
+    Unset(OptConfigUnset),
 }
 
 /// Show the current configuration
 #[derive(Args)]
 pub struct OptConfigShow {}
 
+/// Print the value of an entry of the configuration (fails if it is not set)
+#[derive(Args)]
+pub struct OptConfigGet {
+    key: String,
+}
+
 /// Modify an entry of the configuration
 #[derive(Args)]
+#[command(after_help = CONFIG_EXAMPLES)]
 pub struct OptConfigSet {
     key: String,
     value: String,
 }
 
+/// Reset an entry of the configuration to the default value
+#[derive(Args)]
+pub struct OptConfigUnset {
+    key: String,
+}
+
+/// Generate a manifest of installed toolchains
+#[derive(Args)]
+pub struct OptLock {
+    /// Output path of the manifest (stdout if omitted)
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// Show the release information of a given version
+#[derive(Args)]
+pub struct OptReleaseInfo {
+    /// Version or "latest"
+    target: String,
+
+    /// Print the raw information as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Modify components of an installed toolchain
+#[derive(Args)]
+pub struct OptComponent {
+    #[command(subcommand)]
+    command: ComponentCommand,
+}
+
+#[derive(Subcommand)]
+pub enum ComponentCommand {
+    Add(OptComponentAdd),
+    Remove(OptComponentRemove),
+}
+
+/// Add a component to a toolchain
+#[derive(Args)]
+pub struct OptComponentAdd {
+    component: String,
+
+    /// Target toolchain (default toolchain if omitted)
+    #[arg(long)]
+    toolchain: Option<String>,
+
+    /// Toolchain package path for offline installation
+    #[arg(long)]
+    pkg: Option<PathBuf>,
+}
+
+/// Remove a component from a toolchain
+#[derive(Args)]
+pub struct OptComponentRemove {
+    component: String,
+
+    /// Target toolchain (default toolchain if omitted)
+    #[arg(long)]
+    toolchain: Option<String>,
+}
+
+/// Show the overview of verylup and toolchains
+#[derive(Args)]
+pub struct OptStatus {
+    /// Print the status as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Recreate the links of veryl and veryl-ls to verylup
+#[derive(Args)]
+pub struct OptRelink {}
+
+/// Install the toolchain if missing, and print the path of veryl-ls
+#[derive(Args)]
+pub struct OptEnsure {
+    /// Toolchain (project override or default toolchain if omitted)
+    target: Option<String>,
+
+    /// Toolchain package path for offline installation
+    #[arg(long)]
+    pkg: Option<PathBuf>,
+}
+
+/// List toolchain versions available on the release server
+#[derive(Args)]
+pub struct OptListAvailable {
+    /// Show only versions newer than this (the newest installed version if omitted)
+    #[arg(long)]
+    since: Option<Version>,
+
+    /// Show all versions including older ones
+    #[arg(long, conflicts_with = "since")]
+    all: bool,
+
+    /// Show the title of each release
+    #[arg(long)]
+    title: bool,
+}
+
+/// Manage verylup itself
+#[derive(Args)]
+pub struct OptSelf {
+    #[command(subcommand)]
+    command: SelfCommand,
+}
+
+#[derive(Subcommand)]
+enum SelfCommand {
+    UpgradeData(OptSelfUpgradeData),
+    Version(OptSelfVersion),
+}
+
+/// Migrate data created by older verylup to the current layout
+#[derive(Args)]
+pub struct OptSelfUpgradeData {}
+
+/// Show the version of verylup with build information
+#[derive(Args)]
+pub struct OptSelfVersion {}
+
+/// Show the actual path of a given tool
+#[derive(Args)]
+pub struct OptWhich {
+    /// Tool name like "veryl"
+    tool: String,
+
+    /// Target toolchain (default toolchain if omitted)
+    #[arg(long, conflicts_with = "all")]
+    toolchain: Option<String>,
+
+    /// List all executables of the tool found in PATH, and mark the verylup shim
+    #[arg(long)]
+    all: bool,
+}
+
+/// Print the archive URL of a given toolchain
+#[derive(Args)]
+pub struct OptUrl {
+    /// Version or "latest"
+    target: String,
+
+    /// Target triple of the archive (the running platform if omitted)
+    #[arg(long = "target", value_name = "TRIPLE")]
+    triple: Option<String>,
+}
+
+/// Uninstall old versioned toolchains
+///
+/// The default toolchain, overridden toolchains, "latest" and "local" are always kept.
+#[derive(Args)]
+#[command(group(clap::ArgGroup::new("policy").required(true).multiple(true)))]
+pub struct OptGc {
+    /// Keep the newest N versions
+    #[arg(long, value_name = "N", group = "policy")]
+    keep: Option<usize>,
+
+    /// Keep the newest N versions of each minor series like 0.16.x
+    #[arg(long, value_name = "N", group = "policy")]
+    per_minor: Option<usize>,
+
+    /// Print toolchains to be removed without removing them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Compare the actual versions of two installed toolchains
+#[derive(Args)]
+pub struct OptDiff {
+    a: String,
+    b: String,
+}
+
+/// Conventional destination of the completion script of `name` for `shell`
+fn completion_path(shell: &CompletionShell, name: &str) -> String {
+    match shell {
+        CompletionShell::Bash => format!("~/.local/share/bash-completion/completions/{name}"),
+        CompletionShell::Elvish => {
+            format!("~/.config/elvish/lib/{name}.elv and `use {name}` in rc.elv")
+        }
+        CompletionShell::Fish => format!("~/.config/fish/completions/{name}.fish"),
+        CompletionShell::PowerShell => "a file sourced from $PROFILE".to_string(),
+        CompletionShell::Zsh => format!("_{name} in a directory of $fpath like ~/.zfunc"),
+    }
+}
+
+/// Arguments of `veryl` to generate completion scripts
+const DEFAULT_COMPLETION_COMMAND: &str = "check --completion {shell}";
+
+const INSTALL_EXAMPLES: &str = "\
+Examples:
+  verylup install latest
+  verylup install 0.16.0 --set-default
+  verylup install 0.16.0 --pkg veryl-x86_64-linux.zip
+  verylup install --manifest toolchains.lock
+  verylup install local --no-build";
+
+const OVERRIDE_EXAMPLES: &str = "\
+Examples:
+  cd path/to/project && verylup override set 0.16.0
+  verylup override list
+  cd path/to/project && verylup override unset
+
+The override is set to the current directory, which must be inside a Veryl project,
+and applies when tools are executed in exactly that directory.";
+
+const CONFIG_EXAMPLES: &str = "\
+Examples:
+  verylup config show
+  verylup config get proxy
+  verylup config set offline true
+  verylup config set mirror https://mirror.example.com/veryl-lang
+  verylup config set update_reminder_interval 72
+  verylup config unset mirror";
+
 impl std::fmt::Display for CompletionShell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let text = match self {
@@ -177,9 +536,30 @@ pub enum CompletionCommand {
     Veryl,
 }
 
-pub async fn main() -> Result<()> {
+pub async fn main() -> Result<ExitCode> {
     let opt = Opt::parse();
+    let message_format = opt.message_format;
 
+    match run(opt).await {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(err) if message_format == MessageFormat::Json => {
+            let json = serde_json::json!({
+                "error": {
+                    "code": 1,
+                    "message": format!("{err:#}"),
+                }
+            });
+            eprintln!("{json}");
+            Ok(ExitCode::FAILURE)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+async fn run(opt: Opt) -> Result<()> {
+    if let Some(x) = opt.config {
+        Config::set_path(x);
+    }
     let level = if opt.verbose {
         LevelFilter::Debug
     } else if opt.quiet {
@@ -188,86 +568,297 @@ pub async fn main() -> Result<()> {
         LevelFilter::Info
     };
 
-    Dispatch::new()
-        .format(|out, message, record| {
-            let style = match record.level() {
-                Level::Error => Style::new().red().bright(),
-                Level::Warn => Style::new().yellow().bright(),
-                Level::Info => Style::new().green().bright(),
-                Level::Debug => Style::new().cyan().bright(),
-                Level::Trace => Style::new().magenta().bright(),
-            };
-            out.finish(format_args!(
-                "{} {}{}",
-                style.apply_to(format!("[{:<5}]", record.level())),
-                " ".repeat(
-                    12 - format!("{message}")
-                        .split_ascii_whitespace()
-                        .next()
-                        .unwrap()
-                        .len()
-                ),
-                message
-            ))
-        })
-        .level(level)
-        .chain(std::io::stderr())
-        .apply()?;
+    init_logger(level)?;
+    check_pinned_verylup_version();
+
+    let yes = env::var("VERYLUP_YES").is_ok_and(|x| !x.is_empty() && x != "0");
+    set_assume_yes(opt.yes || yes);
+    set_json_progress(opt.message_format == MessageFormat::Json);
+
+    // commands modifying toolchains or verylup itself are serialized
+    let _lock = match &opt.command {
+        Commands::Update(x) if !x.dry_run => Some(Lock::acquire()?),
+        Commands::Install(_)
+        | Commands::Uninstall(_)
+        | Commands::Setup(_)
+        | Commands::Component(_)
+        | Commands::Relink(_) => Some(Lock::acquire()?),
+        Commands::SelfCommand(x) if matches!(x.command, SelfCommand::UpgradeData(_)) => {
+            Some(Lock::acquire()?)
+        }
+        Commands::Gc(x) if !x.dry_run => Some(Lock::acquire()?),
+        _ => None,
+    };
+
+    // data created by older verylup is migrated automatically once
+    if _lock.is_some() && State::load().data_version < DATA_VERSION {
+        if let Err(x) = upgrade_data(false) {
+            warn!("failed to migrate data: {x}, run `verylup self upgrade-data` to retry");
+            let mut state = State::load();
+            state.data_version = DATA_VERSION;
+            let _ = state.save();
+        }
+    }
 
     match opt.command {
-        Commands::Show(_) => {
+        Commands::Show(x) => {
+            let default_toolchain = ToolChain::default_toolchain();
+            let toolchains = ToolChain::list();
+
+            if x.paths {
+                let separator = if x.print0 { b"\0" } else { b"\n" };
+                let mut stdout = std::io::stdout().lock();
+                for x in &toolchains {
+                    let ret = stdout
+                        .write_all(x.get_dir().as_os_str().as_encoded_bytes())
+                        .and_then(|_| stdout.write_all(separator));
+                    // the reader like `head` may exit early
+                    match ret {
+                        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => break,
+                        x => x?,
+                    }
+                }
+                return Ok(());
+            }
+
+            let state = State::load();
+            let check_binaries = x.check_binaries;
+
+            // versions of channels can't be determined from the directory name
+            let channels: Vec<_> = toolchains
+                .iter()
+                .filter(|x| opt.verbose || check_binaries || !matches!(x, ToolChain::Version(_)))
+                .cloned()
+                .collect();
+            let versions = ToolChain::get_actual_versions(&channels).await;
+            let probe = |x: &ToolChain| channels.iter().position(|y| y == x).map(|i| &versions[i]);
+            let get_version = |x: &ToolChain| probe(x).and_then(|x| x.as_ref().ok().cloned());
+
+            if x.json {
+                let toolchains: Vec<_> = toolchains
+                    .iter()
+                    .map(|x| {
+                        let version = match x {
+                            ToolChain::Version(x) => Some(x.clone()),
+                            _ => get_version(x),
+                        };
+                        let mut json = serde_json::json!({
+                            "name": x.to_string(),
+                            "version": version.map(|x| x.to_string()),
+                            "path": x.get_dir(),
+                            "default": Some(x) == default_toolchain.as_ref(),
+                        });
+                        if check_binaries {
+                            json["runnable"] = probe(x).is_some_and(|x| x.is_ok()).into();
+                        }
+                        json
+                    })
+                    .collect();
+
+                let json = serde_json::json!({
+                    "verylup_version": VERSION,
+                    "data_dir": data_dir(),
+                    "toolchain_dir": ToolChain::base_dir(),
+                    "default_toolchain": default_toolchain.as_ref().map(|x| x.to_string()),
+                    "last_update": state.last_update,
+                    "toolchains": toolchains,
+                });
+                println!("{}", serde_json::to_string_pretty(&json)?);
+                return Ok(());
+            }
+
+            if x.remote && Config::load().is_offline() {
+                info!("offline: showing installed toolchains only");
+            } else if x.remote {
+                match get_available_versions().await {
+                    Ok(available) => {
+                        show_remote(
+                            &toolchains,
+                            default_toolchain.as_ref(),
+                            &available,
+                            get_version,
+                        );
+                        return Ok(());
+                    }
+                    Err(x) => warn!("failed to get available versions: {x}"),
+                }
+                info!("showing installed toolchains only");
+            }
+
+            ToolChain::check_stale_local();
+
+            // the format must be kept stable, see "Scripting" in README
+            if x.porcelain {
+                for x in &toolchains {
+                    let version = match x {
+                        ToolChain::Version(x) => Some(x.clone()),
+                        _ => get_version(x),
+                    };
+                    let version = version.map(|x| x.to_string());
+                    let flags = if Some(x) == default_toolchain.as_ref() {
+                        "default"
+                    } else {
+                        "-"
+                    };
+                    println!("{x}\t{}\t{flags}", version.as_deref().unwrap_or("-"));
+                }
+                return Ok(());
+            }
+
+            println!("verylup: {VERSION}\n");
             println!("installed toolchains");
             println!("--------------------\n");
 
-            let default_toolchain = ToolChain::default_toolchain();
-            for x in ToolChain::list() {
-                let text = if x == ToolChain::Latest {
-                    if let Ok(version) = x.get_actual_version() {
-                        format!("{x}: {version}")
-                    } else {
-                        x.to_string()
-                    }
+            for x in toolchains {
+                let version = get_version(&x).filter(|_| !matches!(x, ToolChain::Version(_)));
+                let text = if let Some(version) = version {
+                    format!("{x}: {version}")
                 } else {
                     x.to_string()
                 };
-                if Some(&x) == default_toolchain.as_ref() {
-                    println!("{text} (default)");
+                let text = if Some(&x) == default_toolchain.as_ref() {
+                    format!("{text} (default)")
+                } else {
+                    text
+                };
+                if check_binaries {
+                    match probe(&x) {
+                        Some(Ok(_)) => println!("{text} [OK]"),
+                        Some(Err(err)) => println!("{text} [FAIL: {err}]"),
+                        None => println!("{text} [FAIL]"),
+                    }
                 } else {
                     println!("{text}");
                 }
+
+                if opt.verbose {
+                    show_toolchain_details(&x, get_version(&x));
+                }
+            }
+
+            if let Some(elapsed) = state.last_update_elapsed() {
+                println!("\nlast updated: {}", format_elapsed(elapsed));
             }
         }
         Commands::Update(x) => {
             let config = Config::load();
-            if x.pkg.is_none() && config.offline {
+            if x.pkg.is_none() && config.is_offline() {
                 bail!("\"--pkg\" is required in offline mode");
             }
 
+            if x.dry_run {
+                return show_update_plan(&x, &config).await;
+            }
+
             let toolchain = ToolChain::Latest;
-            toolchain.install(&x.pkg).await?;
+            let opt = InstallOption {
+                pkg: x.pkg,
+                system: x.system,
+                keep_archive: x.keep_archive,
+                no_verify: x.no_verify,
+                ..Default::default()
+            };
+            let old_version = toolchain.get_actual_version().ok();
+            toolchain.install(&opt).await?;
+            let new_version = toolchain.get_actual_version().ok();
+
+            if let (Some(old_version), Some(new_version)) = (old_version, new_version) {
+                if old_version != new_version {
+                    show_update_summary(&old_version, &new_version, config.is_offline()).await;
+                }
+            }
 
-            if !config.offline {
-                self_update().await?;
+            if config.is_offline() {
+                // self-update requires network
+            } else if !config.self_update {
+                info!("skipping verylup update: disabled by \"self_update\" config");
+                refresh_latest_verylup_version().await;
+            } else if let Some(x) = pinned_verylup_version() {
+                info!("skipping verylup update: pinned to {x} by the project");
+                refresh_latest_verylup_version().await;
+            } else {
+                self_update(x.no_verify).await?;
             }
+
+            let mut state = State::load();
+            state.record_update();
+            state.save()?;
         }
         Commands::Install(x) => {
             let config = Config::load();
-            if x.pkg.is_none() && config.offline {
+            if x.pkg.is_none() && config.is_offline() {
                 bail!("\"--pkg\" is required in offline mode");
             }
 
-            let toolchain = ToolChain::try_from(&x.target)?;
-            toolchain.install(&x.pkg).await?;
+            if let Some(path) = &x.manifest {
+                let manifest = Manifest::load(path)?;
+                for entry in &manifest.toolchains {
+                    let toolchain = ToolChain::Version(entry.version.clone());
+                    let opt = InstallOption {
+                        system: x.system,
+                        sha256: Some(entry.get_sha256()?),
+                        keep_archive: x.keep_archive.clone(),
+                        no_verify: x.no_verify,
+                        ..Default::default()
+                    };
+                    toolchain.install(&opt).await?;
+                }
+                return Ok(());
+            }
+
+            let toolchain = ToolChain::parse(x.target.as_ref().unwrap())?;
+            if x.no_build && toolchain != ToolChain::Local {
+                bail!("\"--no-build\" can be used for \"local\" toolchain only");
+            }
+            let opt = InstallOption {
+                pkg: x.pkg,
+                system: x.system,
+                no_build: x.no_build,
+                keep_archive: x.keep_archive,
+                no_verify: x.no_verify,
+                assume_version: x.assume_version,
+                smoke_test: x.smoke_test,
+                no_verify_signature: x.no_verify_signature,
+                ..Default::default()
+            };
+
+            if let Some(dir) = &x.output_dir {
+                toolchain.extract_to(dir, &opt).await?;
+                return Ok(());
+            }
+
+            toolchain.install(&opt).await?;
+            ToolChain::check_stale_local();
+
+            if x.set_default {
+                let mut config = Config::load();
+                config.default_toolchain = Some(toolchain.to_string());
+                info!("setting default toolchain: {toolchain}");
+                config.save()?;
+            }
         }
         Commands::Uninstall(x) => {
-            let toolchain = ToolChain::try_from(&x.target)?;
+            let toolchain = ToolChain::parse(&x.target)?;
+            if toolchain.exists() && !confirm(&format!("uninstall toolchain \"{toolchain}\"?"))? {
+                info!("canceled: toolchain \"{toolchain}\" is kept");
+                return Ok(());
+            }
             toolchain.uninstall()?;
         }
         Commands::Default(x) => {
-            let toolchain = ToolChain::try_from(&x.target)?;
             let mut config = Config::load();
-            config.default_toolchain = Some(toolchain.to_string());
-            config.save()?;
+            if x.unset {
+                if config.default_toolchain.take().is_some() {
+                    info!("removing default toolchain");
+                    config.save()?;
+                } else {
+                    info!("no default toolchain is configured");
+                }
+            } else {
+                let toolchain = ToolChain::parse(x.target.as_ref().unwrap())?;
+                config.default_toolchain = Some(toolchain.to_string());
+                config.save()?;
+            }
         }
         Commands::Override(x) => {
             let mut config = Config::load();
@@ -279,7 +870,7 @@ pub async fn main() -> Result<()> {
                     }
                 }
                 OverrideCommand::Set(x) => {
-                    let toolchain = ToolChain::try_from(&x.target)?;
+                    let toolchain = ToolChain::parse(&x.target)?;
                     let dir = search_project()?;
                     config.overrides.insert(dir.clone(), toolchain.to_string());
                     info!("adding toolchain override for {}", dir.to_string_lossy());
@@ -308,99 +899,831 @@ pub async fn main() -> Result<()> {
             }
 
             let toolchain = ToolChain::Latest;
-            toolchain.install(&x.pkg).await?;
-            let self_path = env::current_exe()?;
+            let opt = InstallOption {
+                pkg: x.pkg,
+                ..Default::default()
+            };
+            toolchain.install(&opt).await?;
+            let self_path = self_path()?;
             update_link(&self_path)?;
         }
-        Commands::Completion(x) => match x.command {
-            CompletionCommand::Verylup => {
-                let shell = match x.shell {
-                    CompletionShell::Bash => Shell::Bash,
-                    CompletionShell::Elvish => Shell::Elvish,
-                    CompletionShell::Fish => Shell::Fish,
-                    CompletionShell::PowerShell => Shell::PowerShell,
-                    CompletionShell::Zsh => Shell::Zsh,
-                };
-                clap_complete::generate(
-                    shell,
-                    &mut Opt::command(),
-                    "verylup",
-                    &mut std::io::stdout(),
-                );
-            }
-            CompletionCommand::Veryl => {
-                let toolchain =
-                    ToolChain::default_toolchain().ok_or(anyhow!("no toolchain is found"))?;
-                let mut cmd = std::process::Command::new(toolchain.get_path("veryl"));
-                cmd.arg("check")
-                    .arg("--completion")
-                    .arg(x.shell.to_string());
-                exec(&mut cmd)?;
+        Commands::Completion(x) => {
+            let name = match x.command {
+                CompletionCommand::Verylup => "verylup",
+                CompletionCommand::Veryl => "veryl",
+            };
+            match x.command {
+                CompletionCommand::Verylup => {
+                    let shell = match x.shell {
+                        CompletionShell::Bash => Shell::Bash,
+                        CompletionShell::Elvish => Shell::Elvish,
+                        CompletionShell::Fish => Shell::Fish,
+                        CompletionShell::PowerShell => Shell::PowerShell,
+                        CompletionShell::Zsh => Shell::Zsh,
+                    };
+                    clap_complete::generate(
+                        shell,
+                        &mut Opt::command(),
+                        "verylup",
+                        &mut std::io::stdout(),
+                    );
+                }
+                CompletionCommand::Veryl => {
+                    let toolchain = ToolChain::get_default_toolchain()?;
+                    let args = Config::load()
+                        .completion_command
+                        .unwrap_or(DEFAULT_COMPLETION_COMMAND.to_string())
+                        .replace("{shell}", &x.shell.to_string());
+                    let status = std::process::Command::new(toolchain.get_path("veryl"))
+                        .args(args.split_whitespace())
+                        .status()?;
+                    if !status.success() {
+                        bail!(
+                        "`veryl {args}` failed ({status}) in toolchain \"{toolchain}\", the command can be changed by `verylup config set completion_command <args>`"
+                    );
+                    }
+                }
             }
-        },
+            info!(
+                "hint: save the script as {}",
+                completion_path(&x.shell, name)
+            );
+        }
         Commands::Config(x) => match x.command {
             ConfigCommand::Show(_) => {
                 let config = Config::load();
                 println!("{config}");
             }
+            ConfigCommand::Get(x) => {
+                let Some(value) = Config::load().get(&x.key)? else {
+                    bail!("\"{}\" is not set", x.key);
+                };
+                println!("{value}");
+            }
             ConfigCommand::Set(x) => {
                 let mut config = Config::load();
                 config.set(&x.key, &x.value)?;
                 config.save()?;
             }
+            ConfigCommand::Unset(x) => {
+                let mut config = Config::load();
+                config.unset(&x.key)?;
+                config.save()?;
+            }
+        },
+        Commands::Component(x) => match x.command {
+            ComponentCommand::Add(x) => {
+                check_component(&x.component)?;
+                let toolchain = get_installed_toolchain(&x.toolchain).await?;
+
+                let config = Config::load();
+                if x.pkg.is_none() && config.is_offline() {
+                    bail!("\"--pkg\" is required in offline mode");
+                }
+
+                toolchain.add_component(&x.component, &x.pkg).await?;
+                let self_path = self_path()?;
+                update_link(&self_path)?;
+            }
+            ComponentCommand::Remove(x) => {
+                check_component(&x.component)?;
+                if x.component == "veryl" {
+                    bail!("component \"veryl\" is required by toolchains");
+                }
+                let toolchain = get_installed_toolchain(&x.toolchain).await?;
+                toolchain.remove_component(&x.component)?;
+            }
+        },
+        Commands::Status(x) => {
+            let config = Config::load();
+            let state = State::load();
+
+            let self_version = Version::parse(VERSION)?;
+            let verylup_update = state
+                .latest_verylup_version
+                .clone()
+                .filter(|x| *x > self_version);
+
+            let default_toolchain = ToolChain::default_toolchain();
+            let default_probe = default_toolchain.as_ref().map(|x| x.get_actual_version());
+            let default_version = default_probe
+                .as_ref()
+                .and_then(|x| x.as_ref().ok().cloned());
+
+            let toolchain_dir = ToolChain::base_dir();
+            let disk_usage = dir_size(&toolchain_dir);
+            let warnings = status_warnings(&config, &default_toolchain, default_probe.as_ref());
+
+            if x.json {
+                let json = serde_json::json!({
+                    "verylup_version": VERSION,
+                    "verylup_update": verylup_update.map(|x| x.to_string()),
+                    "default_toolchain": default_toolchain.as_ref().map(|x| x.to_string()),
+                    "default_version": default_version.map(|x| x.to_string()),
+                    "data_dir": data_dir(),
+                    "toolchain_dir": toolchain_dir,
+                    "disk_usage": disk_usage,
+                    "last_update": state.last_update,
+                    "config": config,
+                    "warnings": warnings,
+                });
+                println!("{}", serde_json::to_string_pretty(&json)?);
+                return Ok(());
+            }
+
+            if let Some(x) = verylup_update {
+                println!("verylup: {VERSION} (update available: {x})");
+            } else {
+                println!("verylup: {VERSION}");
+            }
+
+            match (&default_toolchain, &default_version) {
+                (Some(x), Some(y)) => println!("default toolchain: {x} ({y})"),
+                (Some(x), None) => println!("default toolchain: {x}"),
+                _ => println!("default toolchain: none"),
+            }
+
+            if let Some(x) = portable_dir() {
+                println!("portable: {}", x.to_string_lossy());
+            }
+            println!("data directory: {}", data_dir().to_string_lossy());
+            println!(
+                "toolchain directory: {} ({})",
+                toolchain_dir.to_string_lossy(),
+                format_size(disk_usage)
+            );
+            if let Some(elapsed) = state.last_update_elapsed() {
+                println!("last updated: {}", format_elapsed(elapsed));
+            }
+            println!();
+            print!("{config}");
+
+            if !warnings.is_empty() {
+                println!("\nwarnings:");
+                for x in &warnings {
+                    println!("  - {x}");
+                }
+            }
+        }
+        Commands::Lock(x) => {
+            let manifest = Manifest::generate().await?;
+            if let Some(path) = &x.output {
+                manifest.save(path)?;
+                info!("writing manifest: {}", path.to_string_lossy());
+            } else {
+                println!("{}", manifest.to_json()?);
+            }
+        }
+        Commands::ReleaseInfo(x) => {
+            let version = match ToolChain::parse(&x.target)? {
+                ToolChain::Version(x) => x,
+                ToolChain::Latest => get_latest_toolchain_version().await?,
+                ToolChain::Local => return Err(local_download_error()),
+            };
+
+            let release = get_release("veryl", &version).await?;
+
+            if x.json {
+                println!("{}", serde_json::to_string_pretty(&release)?);
+            } else {
+                println!("{}", release.name.as_ref().unwrap_or(&release.tag_name));
+                println!("  url: {}", release.html_url);
+                if let Some(x) = &release.published_at {
+                    println!("  published: {x}");
+                }
+                println!("  prerelease: {}", release.prerelease);
+                println!("  assets:");
+                for asset in &release.assets {
+                    let digest = asset.digest.as_deref().unwrap_or("-");
+                    println!("    {} ({} bytes, {digest})", asset.name, asset.size);
+                }
+            }
+        }
+        Commands::Relink(_) => {
+            let self_path = self_path()?;
+            update_link(&self_path)?;
+        }
+        Commands::SelfCommand(x) => match x.command {
+            SelfCommand::UpgradeData(_) => upgrade_data(true)?,
+            SelfCommand::Version(_) => {
+                println!("verylup {VERSION}");
+                println!("target: {TARGET}");
+                println!("commit: {}", COMMIT.unwrap_or("unknown"));
+            }
         },
+        Commands::Url(x) => {
+            let version = match ToolChain::parse(&x.target)? {
+                ToolChain::Version(x) => x,
+                ToolChain::Latest => get_latest_toolchain_version().await?,
+                ToolChain::Local => return Err(local_download_error()),
+            };
+            let triple = x.triple.unwrap_or_else(target);
+            println!("{}", get_archive_url_for("veryl", &version, &triple)?);
+        }
+        Commands::Gc(x) => {
+            let targets = gc_targets(x.keep, x.per_minor);
+            if targets.is_empty() {
+                info!("no toolchain to be removed");
+                return Ok(());
+            }
+
+            if x.dry_run {
+                for x in &targets {
+                    println!("{x}");
+                }
+                return Ok(());
+            }
+
+            let names: Vec<_> = targets.iter().map(|x| x.to_string()).collect();
+            if !confirm(&format!("uninstall toolchains {}?", names.join(", ")))? {
+                info!("canceled: toolchains are kept");
+                return Ok(());
+            }
+            for x in &targets {
+                x.uninstall()?;
+            }
+        }
+        Commands::Diff(x) => {
+            let a = get_installed_toolchain(&Some(x.a)).await?;
+            let b = get_installed_toolchain(&Some(x.b)).await?;
+            let get_version = |x: &ToolChain| {
+                x.get_actual_version()
+                    .map_err(|err| anyhow!("toolchain \"{x}\" can't be executed: {err}"))
+            };
+            let version_a = get_version(&a)?;
+            let version_b = get_version(&b)?;
+
+            println!("{a}: {version_a}");
+            println!("{b}: {version_b}");
+
+            let relation = match version_a.cmp(&version_b) {
+                std::cmp::Ordering::Greater => "is newer than",
+                std::cmp::Ordering::Less => "is older than",
+                std::cmp::Ordering::Equal => "is the same version as",
+            };
+            println!("{a} {relation} {b}");
+        }
+        Commands::Which(x) => {
+            if x.all {
+                let shim = self_path()?;
+                let paths = env::var_os("PATH").unwrap_or_default();
+                for dir in env::split_paths(&paths) {
+                    let path = dir.join(exe_name(&x.tool));
+                    if !path.is_file() {
+                        continue;
+                    }
+                    if is_same_file(&path, &shim) {
+                        println!("{} (verylup shim)", path.to_string_lossy());
+                    } else {
+                        println!("{}", path.to_string_lossy());
+                    }
+                }
+            } else {
+                check_component(&x.tool)?;
+                let toolchain = get_installed_toolchain(&x.toolchain).await?;
+                let path = toolchain.get_path(&exe_name(&x.tool));
+                if !path.exists() {
+                    bail!("\"{}\" is not found in toolchain \"{toolchain}\"", x.tool);
+                }
+                println!("{}", path.to_string_lossy());
+            }
+        }
+        Commands::ListAvailable(x) => {
+            let since = if x.all {
+                None
+            } else if x.since.is_some() {
+                x.since
+            } else {
+                let toolchains: Vec<_> = ToolChain::list()
+                    .into_iter()
+                    .filter(|x| *x != ToolChain::Local)
+                    .collect();
+                ToolChain::get_actual_versions(&toolchains)
+                    .await
+                    .into_iter()
+                    .filter_map(|x| x.ok())
+                    .max()
+            };
+
+            let include_prerelease = Config::load().include_prerelease;
+            let releases: Vec<_> = get_releases("veryl")
+                .await?
+                .into_iter()
+                .filter(|x| include_prerelease || !x.prerelease)
+                .filter_map(|x| {
+                    let version = parse_tag(&x.tag_name).ok()?;
+                    Some((version, x))
+                })
+                .collect();
+            if let Some(x) = releases.iter().map(|(version, _)| version).max() {
+                State::cache_latest_version(x);
+            }
+
+            let mut releases: Vec<_> = releases
+                .into_iter()
+                .filter(|(version, _)| since.as_ref().is_none_or(|x| version > x))
+                .collect();
+            releases.sort_by(|x, y| x.0.cmp(&y.0));
+
+            if releases.is_empty() {
+                if let Some(x) = since {
+                    info!("no release newer than {x}");
+                }
+            }
+
+            for (version, release) in &releases {
+                match &release.name {
+                    Some(name) if x.title => println!("{version}: {name}"),
+                    _ => println!("{version}"),
+                }
+            }
+        }
+        Commands::Ensure(x) => {
+            let toolchain = match &x.target {
+                Some(x) => ToolChain::parse(x)?,
+                None => ToolChain::override_toolchain()
+                    .or_else(ToolChain::default_toolchain)
+                    .unwrap_or(ToolChain::Latest),
+            };
+
+            // the lock is taken only to install, so concurrent editors don't block each other
+            if !toolchain.exists() {
+                let _lock = Lock::acquire()?;
+                if !toolchain.exists() {
+                    if x.pkg.is_none() && Config::load().is_offline() {
+                        bail!("\"--pkg\" is required in offline mode");
+                    }
+                    let opt = InstallOption {
+                        pkg: x.pkg,
+                        ..Default::default()
+                    };
+                    toolchain.install(&opt).await?;
+                }
+            }
+
+            let path = toolchain.get_path(&exe_name("veryl-ls"));
+            if !path.exists() {
+                bail!(
+                    "veryl-ls is not found in toolchain \"{toolchain}\", run `verylup component add veryl-ls --toolchain {toolchain}`"
+                );
+            }
+            println!("{}", std::path::absolute(path)?.to_string_lossy());
+        }
     }
 
     Ok(())
 }
 
+/// Released versions of Veryl, filtered by `include_prerelease`
+async fn get_available_versions() -> Result<Vec<Version>> {
+    let config = Config::load();
+    let mut ret: Vec<_> = get_releases("veryl")
+        .await?
+        .into_iter()
+        .filter(|x| config.include_prerelease || !x.prerelease)
+        .filter_map(|x| parse_tag(&x.tag_name).ok())
+        .collect();
+    ret.sort();
+    if let Some(x) = ret.last() {
+        State::cache_latest_version(x);
+    }
+    Ok(ret)
+}
+
+fn show_remote(
+    toolchains: &[ToolChain],
+    default_toolchain: Option<&ToolChain>,
+    available: &[Version],
+    get_version: impl Fn(&ToolChain) -> Option<Version>,
+) {
+    let newest = available.last();
+
+    println!("verylup: {VERSION}\n");
+    println!("toolchains");
+    println!("----------\n");
+
+    let mut versions: Vec<_> = available.to_vec();
+    for x in toolchains {
+        match x {
+            ToolChain::Version(x) => versions.push(x.clone()),
+            x => {
+                let version = get_version(x);
+                let mut marks = vec!["installed"];
+                if Some(x) == default_toolchain {
+                    marks.push("default");
+                }
+                let outdated = *x == ToolChain::Latest
+                    && matches!((&version, newest), (Some(x), Some(y)) if x < y);
+                if outdated {
+                    marks.push("outdated");
+                }
+                match version {
+                    Some(version) => println!("{x}: {version} ({})", marks.join(", ")),
+                    None => println!("{x} ({})", marks.join(", ")),
+                }
+            }
+        }
+    }
+
+    versions.sort();
+    versions.dedup();
+    for version in versions {
+        let toolchain = ToolChain::Version(version.clone());
+        let mut marks = vec![];
+        if toolchains.contains(&toolchain) {
+            marks.push("installed");
+        }
+        if Some(&toolchain) == default_toolchain {
+            marks.push("default");
+        }
+        if !available.contains(&version) {
+            marks.push("not released");
+        }
+        if marks.is_empty() {
+            println!("{version}");
+        } else {
+            println!("{version} ({})", marks.join(", "));
+        }
+    }
+}
+
+fn show_toolchain_details(toolchain: &ToolChain, version: Option<Version>) {
+    let dir = toolchain.get_dir();
+    let info = toolchain.get_install_info();
+
+    // fallback to the modified time of the directory for toolchains installed by older verylup
+    let installed_at = info
+        .as_ref()
+        .map(|x| UNIX_EPOCH + Duration::from_secs(x.installed_at))
+        .or_else(|| fs::metadata(&dir).and_then(|x| x.modified()).ok())
+        .and_then(|x| SystemTime::now().duration_since(x).ok());
+
+    let unknown = "unknown".to_string();
+    println!("  path: {}", dir.to_string_lossy());
+    println!(
+        "  version: {}",
+        version.map(|x| x.to_string()).unwrap_or(unknown.clone())
+    );
+    println!(
+        "  installed: {}",
+        installed_at.map(format_elapsed).unwrap_or(unknown.clone())
+    );
+    println!(
+        "  source: {}",
+        info.map(|x| x.source).unwrap_or(unknown.clone())
+    );
+    println!("  size: {}", format_size(dir_size(&dir)));
+    println!("  components: {}", toolchain.get_components().join(", "));
+}
+
+fn status_warnings(
+    config: &Config,
+    default_toolchain: &Option<ToolChain>,
+    default_probe: Option<&Result<Version>>,
+) -> Vec<String> {
+    let mut ret = Vec::new();
+
+    if ToolChain::list().is_empty() {
+        ret.push("no toolchain is installed, run `verylup update`".to_string());
+    }
+
+    if let Some(x) = &config.default_toolchain {
+        if ToolChain::by_name(x).is_none() {
+            ret.push(format!(
+                "configured default toolchain \"{x}\" is not installed"
+            ));
+        }
+    }
+
+    for (path, x) in &config.overrides {
+        if ToolChain::by_name(x).is_none() {
+            ret.push(format!(
+                "override toolchain \"{x}\" for {} is not installed",
+                path.to_string_lossy()
+            ));
+        }
+    }
+
+    if let (Some(x), Some(Err(err))) = (default_toolchain, default_probe) {
+        ret.push(format!(
+            "default toolchain \"{x}\" can't be executed ({err})"
+        ));
+    }
+
+    if let Ok(self_path) = self_path() {
+        for tool in tools() {
+            let path = self_path.parent().unwrap().join(exe_name(&tool));
+            if !path.exists() {
+                ret.push(format!(
+                    "shim \"{}\" is not found, run `verylup setup`",
+                    path.to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    ret
+}
+
+const RELEASE_NOTE_LINES: usize = 10;
+
+async fn show_update_summary(old_version: &Version, new_version: &Version, offline: bool) {
+    info!("updated toolchain: {old_version} -> {new_version}");
+    info!("release notes: {}", get_release_url("veryl", new_version));
+
+    // release notes are printed to stdout, so they are suppressed by `--quiet` like logs
+    if offline || log::max_level() < LevelFilter::Info {
+        return;
+    }
+
+    if let Ok(release) = get_release("veryl", new_version).await {
+        if let Some(body) = release.body {
+            let lines: Vec<_> = body.lines().filter(|x| !x.trim().is_empty()).collect();
+            println!();
+            for line in lines.iter().take(RELEASE_NOTE_LINES) {
+                println!("  {line}");
+            }
+            if lines.len() > RELEASE_NOTE_LINES {
+                println!("  ...");
+            }
+            println!();
+        }
+    }
+}
+
+/// Versioned toolchains which are not retained by the given policies
+///
+/// A toolchain is retained if any policy keeps it.
+fn gc_targets(keep: Option<usize>, per_minor: Option<usize>) -> Vec<ToolChain> {
+    let config = Config::load();
+    let mut protected: Vec<_> = config
+        .overrides
+        .values()
+        .filter_map(|x| ToolChain::try_from(x).ok())
+        .collect();
+    protected.extend(ToolChain::default_toolchain());
+
+    let mut versions: Vec<_> = ToolChain::list()
+        .into_iter()
+        .filter_map(|x| match x {
+            ToolChain::Version(x) => Some(x),
+            _ => None,
+        })
+        .collect();
+    versions.sort_by(|x, y| y.cmp(x));
+
+    let mut retained = Vec::new();
+    if let Some(n) = keep {
+        retained.extend(versions.iter().take(n).cloned());
+    }
+    if let Some(n) = per_minor {
+        let mut count = std::collections::HashMap::new();
+        for x in &versions {
+            let count = count.entry((x.major, x.minor)).or_insert(0);
+            if *count < n {
+                retained.push(x.clone());
+            }
+            *count += 1;
+        }
+    }
+
+    versions
+        .into_iter()
+        .rev()
+        .filter(|x| !retained.contains(x))
+        .map(ToolChain::Version)
+        .filter(|x| !protected.contains(x))
+        .collect()
+}
+
+fn check_component(name: &str) -> Result<()> {
+    let tools = tools();
+    if !tools.iter().any(|x| x == name) {
+        bail!(
+            "unknown component \"{name}\", available components: {}",
+            tools.join(", ")
+        );
+    }
+    Ok(())
+}
+
+async fn get_installed_toolchain(name: &Option<String>) -> Result<ToolChain> {
+    let toolchain = if let Some(x) = name {
+        ToolChain::parse(x)?
+    } else {
+        ToolChain::get_default_toolchain()?
+    };
+
+    // the toolchain may be installed under another name
+    let toolchain = toolchain.resolve_installed().await;
+
+    if !toolchain.exists() {
+        bail!("toolchain \"{toolchain}\" is not found");
+    }
+
+    Ok(toolchain)
+}
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-async fn self_update() -> Result<()> {
-    let latest_version = get_latest_version("verylup").await?;
+/// Print the plan of `verylup update` without changing anything
+async fn show_update_plan(opt: &OptUpdate, config: &Config) -> Result<()> {
+    let toolchain = ToolChain::Latest;
+    let current = toolchain.get_actual_version().ok();
+    let current_text = current
+        .as_ref()
+        .map(|x| x.to_string())
+        .unwrap_or("not installed".to_string());
+    let mut total = Some(0);
+
+    if let Some(pkg) = &opt.pkg {
+        if pkg.is_dir() {
+            println!(
+                "toolchain {toolchain}: {current_text} -> newest package in {}",
+                pkg.to_string_lossy()
+            );
+        } else {
+            let target = get_package_version(pkg)?;
+            println!("toolchain {toolchain}: {current_text} -> {target}");
+            println!("  package: {}", pkg.to_string_lossy());
+        }
+    } else {
+        let target = get_latest_toolchain_version().await?;
+        if current.as_ref() == Some(&target) {
+            println!("toolchain {toolchain}: {current_text} (up-to-date)");
+        } else {
+            println!("toolchain {toolchain}: {current_text} -> {target}");
+            let url = toolchain.get_archive_url(&target)?;
+            let size = get_content_length(&url).await.ok().flatten();
+            print_download(&url, size);
+            total = total.zip(size).map(|(x, y)| x + y);
+        }
+    }
+
+    if config.is_offline() {
+        println!("verylup: {VERSION} (self-update is skipped in offline mode)");
+    } else if !config.self_update {
+        println!("verylup: {VERSION} (self-update is disabled by \"self_update\" config)");
+    } else if let Some(x) = pinned_verylup_version() {
+        println!("verylup: {VERSION} (self-update is skipped, pinned to {x} by the project)");
+    } else {
+        let target = get_latest_verylup_version().await?;
+        if target > Version::parse(VERSION)? {
+            println!("verylup: {VERSION} -> {target}");
+            let url = get_archive_url("verylup", &target)?;
+            let size = get_content_length(&url).await.ok().flatten();
+            print_download(&url, size);
+            total = total.zip(size).map(|(x, y)| x + y);
+        } else {
+            println!("verylup: {VERSION} (up-to-date)");
+        }
+    }
+
+    match total {
+        Some(x) => println!("\ntotal download size: {}", format_size(x)),
+        None => println!("\ntotal download size: unknown"),
+    }
+
+    Ok(())
+}
+
+fn print_download(url: &reqwest::Url, size: Option<u64>) {
+    println!("  url: {url}");
+    match size {
+        Some(x) => println!("  size: {}", format_size(x)),
+        None => println!("  size: unknown"),
+    }
+}
+
+/// Version of the data layout
+///
+/// It should be incremented when a migration is added to `upgrade_data`.
+const DATA_VERSION: u32 = 1;
+
+/// Migrate data created by older verylup idempotently
+///
+/// Nothing is reported unless a migration runs or it is invoked `explicit`ly by the user.
+fn upgrade_data(explicit: bool) -> Result<()> {
+    let mut changed = false;
+
+    // version 1: install information sidecar
+    for x in ToolChain::list() {
+        changed |= x.upgrade_data()?;
+    }
+
+    if !changed && explicit {
+        info!("checking data: up-to-date");
+    } else if !changed {
+        debug!("checking data: up-to-date");
+    }
+
+    let mut state = State::load();
+    state.data_version = DATA_VERSION;
+    state.save()?;
+    Ok(())
+}
+
+/// Resolve the latest verylup version, and cache it for `verylup status`
+async fn get_latest_verylup_version() -> Result<Version> {
+    let version = get_latest_version("verylup").await?;
+    State::cache_latest_verylup_version(&version);
+    Ok(version)
+}
+
+/// Refresh the cached latest verylup version even if self-update is skipped
+///
+/// The cache is optional, so failing to get the version is not an error.
+async fn refresh_latest_verylup_version() {
+    if let Err(err) = get_latest_verylup_version().await {
+        debug!("failed to get the latest verylup version: {err}");
+    }
+}
+
+async fn self_update(no_verify: bool) -> Result<()> {
+    let latest_version = get_latest_verylup_version().await?;
     let self_version = Version::parse(VERSION)?;
 
     if latest_version > self_version {
         info!("downloading verylup: {latest_version}");
 
         let url = get_archive_url("verylup", &latest_version)?;
-        let data = download(&url).await?;
-        let mut file = tempfile::tempfile()?;
-        file.write_all(&data)?;
+        let opt = InstallOption {
+            no_verify,
+            ..Default::default()
+        };
+        let file = download_archive(url, latest_version.clone(), opt).await?;
 
         info!("installing verylup: {latest_version}");
 
-        let dir = tempfile::tempdir()?;
+        let dir = tempfile::tempdir_in(temp_dir()?)?;
 
         unzip(&file, dir.path())?;
 
         let binary = dir.path().join("verylup");
+        set_exec(&mut File::open(&binary)?)?;
 
-        // save self_path before replacing
-        let self_path = env::current_exe()?;
+        // save self_path and links before replacing
+        let self_path = self_path()?;
+        let tools = tools();
+        let linked = linked_tools(&self_path, &tools);
 
         self_replace::self_replace(binary)?;
-        update_link(&self_path)?;
+        update_link_with(&self_path, &tools, &linked)?;
     } else {
         info!("checking verylup: {self_version} (up-to-date)");
     }
     Ok(())
 }
 
+fn tool_link_path(self_path: &Path, tool: &str) -> PathBuf {
+    self_path.parent().unwrap().join(exe_name(tool))
+}
+
+/// Paths of tools which are linked to verylup currently
+fn linked_tools(self_path: &Path, tools: &[String]) -> Vec<PathBuf> {
+    tools
+        .iter()
+        .map(|x| tool_link_path(self_path, x))
+        .filter(|x| is_same_file(self_path, x))
+        .collect()
+}
+
 fn update_link(self_path: &Path) -> Result<()> {
+    update_link_with(self_path, &tools(), &[])
+}
+
+/// Link tools to verylup
+///
+/// Shims of built-in `TOOLS` are always replaced because they may be links to an older verylup.
+/// Existing files of other tools are replaced only if they are included in `linked`,
+/// so unrelated binaries placed next to verylup are never removed.
+fn update_link_with(self_path: &Path, tools: &[String], linked: &[PathBuf]) -> Result<()> {
     let self_path = self_path.canonicalize()?;
 
-    for tool in TOOLS {
-        info!("creating hardlink: {tool}");
+    ensure_writable(
+        self_path.parent().unwrap(),
+        "place verylup in a writable directory",
+    )?;
 
-        let mut tool_path = self_path.parent().unwrap().join(tool);
-        if cfg!(target_os = "windows") {
-            tool_path.set_extension("exe");
-        }
+    for tool in tools {
+        let tool_path = tool_link_path(&self_path, tool);
         if tool_path.exists() {
+            if is_same_file(&self_path, &tool_path) {
+                continue;
+            }
+            if !TOOLS.contains(&tool.as_str()) && !linked.contains(&tool_path) {
+                warn!(
+                    "skipping link: {} is not verylup, remove it to create the shim of {tool}",
+                    tool_path.to_string_lossy()
+                );
+                continue;
+            }
+
+            info!("creating hardlink: {tool}");
             fs::remove_file(&tool_path)?;
             fs::hard_link(&self_path, &tool_path)?;
         } else {
+            info!("creating hardlink: {tool}");
             fs::hard_link(&self_path, &tool_path)?;
         }
     }
@@ -1,12 +1,16 @@
-use anyhow::{bail, Result};
+use crate::utils::{ensure_writable, portable_dir, PORTABLE_MARKER};
+use anyhow::{anyhow, Result};
 use log::info;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub default_toolchain: Option<String>,
@@ -16,13 +20,216 @@ pub struct Config {
 
     #[serde(default)]
     pub offline: bool,
+
+    /// Disable the fallback to the newest installed toolchain
+    #[serde(default)]
+    pub require_explicit_default: bool,
+
+    /// Whether `latest` includes prereleases
+    #[serde(default)]
+    pub include_prerelease: bool,
+
+    /// Directory where toolchains are installed
+    #[serde(default)]
+    pub toolchain_dir: Option<PathBuf>,
+
+    /// Directory for temporary files of downloads and extraction instead of the system one
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Client certificate for mutual TLS (PEM)
+    #[serde(default)]
+    pub client_cert: Option<PathBuf>,
+
+    /// Private key of the client certificate (PEM)
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+
+    /// Additional CA certificates to trust (PEM)
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Proxy URL for HTTP requests
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// File containing the proxy credentials as `user:password`
+    #[serde(default)]
+    pub proxy_auth_file: Option<PathBuf>,
+
+    /// Base URL replacing `https://github.com/veryl-lang` to download releases
+    #[serde(default)]
+    pub release_base_url: Option<String>,
+
+    /// Base URL replacing `https://api.github.com/repos/veryl-lang` to get release information
+    #[serde(default)]
+    pub release_api_url: Option<String>,
+
+    /// Mirror of `https://github.com/veryl-lang` to download releases
+    #[serde(default)]
+    pub mirror: Option<String>,
+
+    /// Try the mirror before GitHub
+    #[serde(default = "default_mirror_first")]
+    pub mirror_first: bool,
+
+    /// User-Agent of HTTP requests instead of `verylup/<version>`
+    #[serde(default)]
+    pub user_agent: Option<String>,
+
+    /// Timeout of establishing connections in seconds (0 means no timeout)
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Timeout of whole HTTP requests including the response body in seconds (0 means no timeout)
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+
+    /// Verify checksums of downloaded archives
+    #[serde(default = "default_verify_downloads", alias = "verify_checksums")]
+    pub verify_downloads: bool,
+
+    /// Number of retries when a download is interrupted or its checksum mismatches
+    #[serde(default = "default_retries")]
+    pub retries: u64,
+
+    /// Minisign public key (base64) to verify signatures of archives instead of the embedded one
+    #[serde(default)]
+    pub trusted_key: Option<String>,
+
+    /// Update verylup itself by `verylup update`
+    #[serde(default = "default_self_update")]
+    pub self_update: bool,
+
+    /// Arguments of `veryl` to generate completion scripts (`{shell}` is replaced by the shell)
+    #[serde(default)]
+    pub completion_command: Option<String>,
+
+    /// Additional tools to be proxied
+    #[serde(default)]
+    pub tools: Vec<String>,
+
+    /// Note in proxy mode when an override selects an older toolchain than the newest installed
+    #[serde(default = "default_downgrade_note")]
+    pub downgrade_note: bool,
+
+    /// Remind in proxy mode when a newer toolchain than the installed `latest` is released
+    #[serde(default = "default_update_reminder")]
+    pub update_reminder: bool,
+
+    /// Minimum interval between update reminders in hours
+    #[serde(default = "default_update_reminder_interval")]
+    pub update_reminder_interval: u64,
+}
+
+fn default_mirror_first() -> bool {
+    true
+}
+
+fn default_connect_timeout() -> u64 {
+    30
+}
+
+fn default_request_timeout() -> u64 {
+    300
+}
+
+fn default_verify_downloads() -> bool {
+    true
+}
+
+fn default_retries() -> u64 {
+    1
+}
+
+fn default_self_update() -> bool {
+    true
+}
+
+fn default_downgrade_note() -> bool {
+    true
+}
+
+fn default_update_reminder() -> bool {
+    true
+}
+
+fn default_update_reminder_interval() -> u64 {
+    24
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_toolchain: None,
+            overrides: HashMap::new(),
+            offline: false,
+            require_explicit_default: false,
+            include_prerelease: false,
+            toolchain_dir: None,
+            temp_dir: None,
+            client_cert: None,
+            client_key: None,
+            ca_bundle: None,
+            proxy: None,
+            proxy_auth_file: None,
+            release_base_url: None,
+            release_api_url: None,
+            mirror: None,
+            mirror_first: default_mirror_first(),
+            user_agent: None,
+            connect_timeout: default_connect_timeout(),
+            request_timeout: default_request_timeout(),
+            verify_downloads: default_verify_downloads(),
+            retries: default_retries(),
+            trusted_key: None,
+            self_update: default_self_update(),
+            completion_command: None,
+            tools: Vec::new(),
+            downgrade_note: default_downgrade_note(),
+            update_reminder: default_update_reminder(),
+            update_reminder_interval: default_update_reminder_interval(),
+        }
+    }
 }
 
 impl Config {
-    pub fn load() -> Self {
-        let path = directories::ProjectDirs::from("com.github", "veryl-lang", "verylup")
+    /// Use an alternate configuration file instead of the default path
+    pub fn set_path(path: PathBuf) {
+        let _ = CONFIG_PATH.set(path);
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        if let Some(x) = CONFIG_PATH.get() {
+            return Some(x.clone());
+        }
+
+        if let Some(x) = portable_dir() {
+            return Some(x.join(PORTABLE_MARKER));
+        }
+
+        directories::ProjectDirs::from("com.github", "veryl-lang", "verylup")
             .map(|proj| proj.preference_dir().join("config.toml"))
-            .filter(|path| path.exists());
+    }
+
+    /// Machine-wide configuration managed by administrators
+    pub fn system_path() -> Option<PathBuf> {
+        if cfg!(target_os = "windows") {
+            std::env::var_os("ProgramData")
+                .map(|x| PathBuf::from(x).join("verylup").join("config.toml"))
+        } else {
+            Some(PathBuf::from("/etc/verylup/config.toml"))
+        }
+    }
+
+    /// Load the machine-wide configuration, returns `None` if it doesn't exist
+    pub fn load_system() -> Option<Self> {
+        let toml = fs::read_to_string(Self::system_path()?).ok()?;
+        toml::from_str(&toml).ok()
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path().filter(|path| path.exists());
 
         let Some(path) = path else {
             return Self::default();
@@ -36,15 +243,13 @@ impl Config {
     }
 
     pub fn save(&self) -> Result<()> {
-        let dir = directories::ProjectDirs::from("com.github", "veryl-lang", "verylup")
-            .map(|proj| proj.preference_dir().to_path_buf());
-
-        if let Some(dir) = dir {
-            if !dir.exists() {
-                fs::create_dir_all(&dir)?;
-            }
+        if let Some(path) = Self::path() {
+            let dir = path.parent().unwrap();
+            ensure_writable(
+                dir,
+                "an alternate configuration file can be specified by `--config`",
+            )?;
 
-            let path = dir.join("config.toml");
             let toml = toml::to_string(self)?;
             fs::write(path, toml)?;
         }
@@ -52,26 +257,248 @@ impl Config {
         Ok(())
     }
 
+    /// Whether offline mode is enabled, `VERYLUP_OFFLINE` overrides the configuration
+    pub fn is_offline(&self) -> bool {
+        match std::env::var("VERYLUP_OFFLINE") {
+            Ok(x) if !x.is_empty() => x != "0",
+            _ => self.offline,
+        }
+    }
+
+    /// Base URL of releases, `VERYLUP_RELEASE_BASE` overrides the configuration
+    pub fn release_base(&self) -> Option<String> {
+        match std::env::var("VERYLUP_RELEASE_BASE") {
+            Ok(x) if !x.is_empty() => Some(x),
+            _ => self.release_base_url.clone(),
+        }
+    }
+
+    /// Base URL of the release API, `VERYLUP_RELEASE_API` overrides the configuration
+    pub fn release_api(&self) -> Option<String> {
+        match std::env::var("VERYLUP_RELEASE_API") {
+            Ok(x) if !x.is_empty() => Some(x),
+            _ => self.release_api_url.clone(),
+        }
+    }
+
     pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
-        match key {
-            "offline" => {
-                let value: bool = value.parse()?;
-                self.offline = value;
-                info!("changed: offline = {value}");
-            }
-            _ => {
-                bail!("Unknown key: {}", key)
-            }
+        let value = match Self::kind(key)? {
+            ValueKind::Bool => toml::Value::Boolean(value.parse()?),
+            ValueKind::Integer => toml::Value::Integer(value.parse::<u64>()?.try_into()?),
+            ValueKind::String | ValueKind::Path => toml::Value::String(value.to_string()),
+            ValueKind::List => toml::Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty())
+                    .map(|x| toml::Value::String(x.to_string()))
+                    .collect(),
+            ),
+        };
+        self.update(key, Some(value.clone()))?;
+
+        match value {
+            toml::Value::String(x) => info!("changed: {key} = {x}"),
+            x => info!("changed: {key} = {x}"),
+        }
+        Ok(())
+    }
+
+    /// Reset an entry to the default value
+    pub fn unset(&mut self, key: &str) -> Result<()> {
+        Self::kind(key)?;
+        self.update(key, None)?;
+        info!("removing: {key}");
+        Ok(())
+    }
+
+    /// Get the raw value of an entry, returns `None` if it is not set
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        if key != "default_toolchain" {
+            Self::kind(key)?;
+        }
+
+        let key = Self::resolve_alias(key);
+        let root = toml::Table::try_from(self)?;
+        let ret = match root.get(key) {
+            Some(toml::Value::String(x)) => Some(x.clone()),
+            Some(toml::Value::Array(x)) if x.is_empty() => None,
+            Some(toml::Value::Array(x)) => Some(
+                x.iter()
+                    .map(|x| {
+                        x.as_str()
+                            .map(String::from)
+                            .unwrap_or_else(|| x.to_string())
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            Some(x) => Some(x.to_string()),
+            None => None,
+        };
+        Ok(ret)
+    }
+
+    fn kind(key: &str) -> Result<ValueKind> {
+        let key = Self::resolve_alias(key);
+        SCHEMA
+            .iter()
+            .find(|(x, _)| *x == key)
+            .map(|(_, x)| *x)
+            .ok_or_else(|| anyhow!("Unknown key: {}", key))
+    }
+
+    fn resolve_alias(key: &str) -> &str {
+        ALIASES
+            .iter()
+            .find(|(x, _)| *x == key)
+            .map(|(_, x)| *x)
+            .unwrap_or(key)
+    }
+
+    /// Replace the entry specified by the key through TOML representation
+    fn update(&mut self, key: &str, value: Option<toml::Value>) -> Result<()> {
+        let key = Self::resolve_alias(key);
+        let mut root = toml::Table::try_from(&*self)?;
+
+        if let Some(value) = value {
+            root.insert(key.to_string(), value);
+        } else {
+            root.remove(key);
         }
+
+        *self = root.try_into()?;
         Ok(())
     }
 }
 
+#[derive(Clone, Copy)]
+enum ValueKind {
+    Bool,
+    Integer,
+    String,
+    Path,
+    /// Comma-separated list of strings
+    List,
+}
+
+/// Keys which can be modified by `verylup config set`
+const SCHEMA: &[(&str, ValueKind)] = &[
+    ("offline", ValueKind::Bool),
+    ("require_explicit_default", ValueKind::Bool),
+    ("include_prerelease", ValueKind::Bool),
+    ("toolchain_dir", ValueKind::Path),
+    ("temp_dir", ValueKind::Path),
+    ("client_cert", ValueKind::Path),
+    ("client_key", ValueKind::Path),
+    ("ca_bundle", ValueKind::Path),
+    ("proxy", ValueKind::String),
+    ("proxy_auth_file", ValueKind::Path),
+    ("release_base_url", ValueKind::String),
+    ("release_api_url", ValueKind::String),
+    ("mirror", ValueKind::String),
+    ("mirror_first", ValueKind::Bool),
+    ("user_agent", ValueKind::String),
+    ("connect_timeout", ValueKind::Integer),
+    ("request_timeout", ValueKind::Integer),
+    ("verify_downloads", ValueKind::Bool),
+    ("retries", ValueKind::Integer),
+    ("trusted_key", ValueKind::String),
+    ("self_update", ValueKind::Bool),
+    ("completion_command", ValueKind::String),
+    ("tools", ValueKind::List),
+    ("downgrade_note", ValueKind::Bool),
+    ("update_reminder", ValueKind::Bool),
+    ("update_reminder_interval", ValueKind::Integer),
+];
+
+/// Alternative names of keys in `SCHEMA`
+const ALIASES: &[(&str, &str)] = &[("verify_checksums", "verify_downloads")];
+
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut ret = String::new();
         ret.push_str("Verylup configuration\n");
-        ret.push_str(&format!("  offline: {}\n", self.offline));
+        if self.is_offline() != self.offline {
+            ret.push_str(&format!(
+                "  offline: {} (overridden by VERYLUP_OFFLINE)\n",
+                self.is_offline()
+            ));
+        } else {
+            ret.push_str(&format!("  offline: {}\n", self.offline));
+        }
+        ret.push_str(&format!(
+            "  require_explicit_default: {}\n",
+            self.require_explicit_default
+        ));
+        ret.push_str(&format!(
+            "  include_prerelease: {}\n",
+            self.include_prerelease
+        ));
+        if let Some(x) = &self.toolchain_dir {
+            ret.push_str(&format!("  toolchain_dir: {}\n", x.to_string_lossy()));
+        }
+        if let Some(x) = &self.temp_dir {
+            ret.push_str(&format!("  temp_dir: {}\n", x.to_string_lossy()));
+        }
+        if let Some(x) = &self.client_cert {
+            ret.push_str(&format!("  client_cert: {}\n", x.to_string_lossy()));
+        }
+        if let Some(x) = &self.client_key {
+            ret.push_str(&format!("  client_key: {}\n", x.to_string_lossy()));
+        }
+        if let Some(x) = &self.ca_bundle {
+            ret.push_str(&format!("  ca_bundle: {}\n", x.to_string_lossy()));
+        }
+        if let Some(x) = &self.proxy {
+            ret.push_str(&format!("  proxy: {x}\n"));
+        }
+        if let Some(x) = &self.proxy_auth_file {
+            ret.push_str(&format!("  proxy_auth_file: {}\n", x.to_string_lossy()));
+        }
+        if self.release_base() != self.release_base_url {
+            ret.push_str(&format!(
+                "  release_base_url: {} (overridden by VERYLUP_RELEASE_BASE)\n",
+                self.release_base().unwrap_or_default()
+            ));
+        } else if let Some(x) = &self.release_base_url {
+            ret.push_str(&format!("  release_base_url: {x}\n"));
+        }
+        if self.release_api() != self.release_api_url {
+            ret.push_str(&format!(
+                "  release_api_url: {} (overridden by VERYLUP_RELEASE_API)\n",
+                self.release_api().unwrap_or_default()
+            ));
+        } else if let Some(x) = &self.release_api_url {
+            ret.push_str(&format!("  release_api_url: {x}\n"));
+        }
+        if let Some(x) = &self.mirror {
+            ret.push_str(&format!("  mirror: {x}\n"));
+            ret.push_str(&format!("  mirror_first: {}\n", self.mirror_first));
+        }
+        if let Some(x) = &self.user_agent {
+            ret.push_str(&format!("  user_agent: {x}\n"));
+        }
+        ret.push_str(&format!("  connect_timeout: {}\n", self.connect_timeout));
+        ret.push_str(&format!("  request_timeout: {}\n", self.request_timeout));
+        ret.push_str(&format!("  verify_downloads: {}\n", self.verify_downloads));
+        ret.push_str(&format!("  retries: {}\n", self.retries));
+        if let Some(x) = &self.trusted_key {
+            ret.push_str(&format!("  trusted_key: {x}\n"));
+        }
+        ret.push_str(&format!("  self_update: {}\n", self.self_update));
+        if let Some(x) = &self.completion_command {
+            ret.push_str(&format!("  completion_command: {x}\n"));
+        }
+        if !self.tools.is_empty() {
+            ret.push_str(&format!("  tools: {}\n", self.tools.join(", ")));
+        }
+        ret.push_str(&format!("  downgrade_note: {}\n", self.downgrade_note));
+        ret.push_str(&format!("  update_reminder: {}\n", self.update_reminder));
+        ret.push_str(&format!(
+            "  update_reminder_interval: {}\n",
+            self.update_reminder_interval
+        ));
         ret.fmt(f)
     }
 }
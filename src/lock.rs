@@ -0,0 +1,129 @@
+use crate::utils::data_dir;
+use anyhow::{bail, Result};
+use log::warn;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Lock to serialize operations modifying toolchains, verylup itself and its links
+///
+/// The lock is a file containing PID of the owner, and it is removed when dropped.
+/// It is held across the self-update binary swap and the following link update,
+/// so other invocations don't observe the intermediate state.
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    fn path() -> PathBuf {
+        data_dir().join("verylup.lock")
+    }
+
+    pub fn acquire() -> Result<Self> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        match Self::create(&path) {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                let pid = fs::read_to_string(&path).unwrap_or_default();
+                let pid = pid.trim();
+                if !Self::is_stale(&path, pid) {
+                    bail!(
+                        "another verylup process (pid: {pid}) is running, remove \"{}\" if it isn't",
+                        path.to_string_lossy()
+                    );
+                }
+
+                warn!(
+                    "breaking stale lock: {} (pid: {pid})",
+                    path.to_string_lossy()
+                );
+                Self::break_stale(&path, pid)?;
+                match Self::create(&path) {
+                    Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                        bail!("another verylup process acquired the lock at the same time")
+                    }
+                    x => Ok(x?),
+                }
+            }
+            x => Ok(x?),
+        }
+    }
+
+    /// Move the stale lock aside atomically
+    ///
+    /// Removing it directly may remove the lock newly acquired by another process breaking it
+    /// concurrently, so it is renamed to a unique name and checked that it is still the stale one.
+    fn break_stale(path: &Path, pid: &str) -> Result<()> {
+        let aside = path.with_extension(format!("lock.{}", std::process::id()));
+        match fs::rename(path, &aside) {
+            // another process has already broken it
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            x => x?,
+        }
+
+        let moved = fs::read_to_string(&aside).unwrap_or_default();
+        if moved.trim() != pid {
+            // the lock acquired by another process was moved, so it is restored
+            let _ = fs::hard_link(&aside, path);
+            let _ = fs::remove_file(&aside);
+            bail!("another verylup process acquired the lock at the same time");
+        }
+        fs::remove_file(&aside)?;
+        Ok(())
+    }
+
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The lock is stale if the owner process doesn't exist, or it is too old
+    fn is_stale(path: &Path, pid: &str) -> bool {
+        if let Some(false) = pid.parse().ok().and_then(process_exists) {
+            return true;
+        }
+
+        fs::metadata(path)
+            .and_then(|x| x.modified())
+            .ok()
+            .and_then(|x| x.elapsed().ok())
+            .is_some_and(|x| x > STALE_LOCK_AGE)
+    }
+}
+
+/// Age of a lock regarded as stale even if the owner seems alive because PID may be reused
+const STALE_LOCK_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Whether the process exists, `None` if it can't be determined
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> Option<bool> {
+    Some(Path::new(&format!("/proc/{pid}")).exists())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_exists(pid: u32) -> Option<bool> {
+    let status = std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .ok()?;
+    Some(status.success())
+}
+
+#[cfg(windows)]
+fn process_exists(_pid: u32) -> Option<bool> {
+    None
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -1,6 +1,9 @@
 mod cli;
 mod config;
 mod exec;
+mod lock;
+mod manifest;
+mod state;
 mod toolchain;
 mod utils;
 
@@ -26,7 +29,7 @@ fn self_name() -> Option<String> {
 async fn main() -> Result<ExitCode> {
     match self_name().as_deref() {
         Some("verylup") => {
-            cli::verylup_mode::main().await?;
+            return cli::verylup_mode::main().await;
         }
         Some(x) => {
             cli::proxy_mode::main(x).await?;
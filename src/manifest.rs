@@ -0,0 +1,103 @@
+use crate::toolchain::ToolChain;
+use crate::utils::*;
+use anyhow::{anyhow, Result};
+use log::info;
+use semver::Version;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Toolchain manifest to install exact toolchains reproducibly
+///
+/// ```json
+/// {
+///   "toolchains": [
+///     {
+///       "version": "0.16.0",
+///       "sha256": {
+///         "veryl-x86_64-linux.zip": "...",
+///         "veryl-x86_64-windows.zip": "..."
+///       }
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub toolchains: Vec<ManifestEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub version: Version,
+
+    /// SHA256 checksums of release archives keyed by archive name
+    #[serde(default)]
+    pub sha256: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Generate a manifest from the installed toolchains
+    ///
+    /// `latest` is recorded as its actual version, and `local` is skipped.
+    pub async fn generate() -> Result<Self> {
+        let mut versions = Vec::new();
+        for toolchain in ToolChain::list() {
+            let version = match toolchain {
+                ToolChain::Version(x) => x,
+                ToolChain::Latest => toolchain.get_actual_version()?,
+                ToolChain::Local => continue,
+            };
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+        versions.sort();
+
+        let mut toolchains = Vec::new();
+        for version in versions {
+            info!("checking release: {version}");
+
+            let release = get_release("veryl", &version).await?;
+            let mut sha256 = BTreeMap::new();
+            for asset in release.assets {
+                if let Some(x) = asset
+                    .digest
+                    .as_ref()
+                    .and_then(|x| x.strip_prefix("sha256:"))
+                {
+                    sha256.insert(asset.name, x.to_string());
+                }
+            }
+            toolchains.push(ManifestEntry { version, sha256 });
+        }
+
+        Ok(Self { toolchains })
+    }
+}
+
+impl ManifestEntry {
+    /// Get the checksum of the archive for the current target
+    pub fn get_sha256(&self) -> Result<String> {
+        let archive = get_archive_name("veryl")?;
+        self.sha256.get(&archive).cloned().ok_or(anyhow!(
+            "checksum of \"{archive}\" for {} is not found in the manifest",
+            self.version
+        ))
+    }
+}
@@ -0,0 +1,128 @@
+use crate::utils::data_dir;
+use anyhow::Result;
+use semver::Version;
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Unix time of the last successful `verylup update`
+    #[serde(default)]
+    pub last_update: Option<u64>,
+
+    /// The latest Veryl version observed on the release server
+    #[serde(default)]
+    pub latest_version: Option<Version>,
+
+    /// The latest verylup version observed on the release server
+    #[serde(default)]
+    pub latest_verylup_version: Option<Version>,
+
+    /// Unix time of the last update reminder check in proxy mode
+    #[serde(default)]
+    pub last_reminder: Option<u64>,
+
+    /// Version of the data layout which was migrated last
+    #[serde(default)]
+    pub data_version: u32,
+}
+
+impl State {
+    fn path() -> Option<PathBuf> {
+        Some(data_dir().join("verylup-state.toml"))
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path().filter(|path| path.exists());
+
+        let Some(path) = path else {
+            return Self::default();
+        };
+
+        let Ok(toml) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+
+        toml::from_str(&toml).unwrap_or_else(|_| Self::default())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(path) = Self::path() {
+            let dir = path.parent().unwrap();
+            if !dir.exists() {
+                fs::create_dir_all(dir)?;
+            }
+
+            let toml = toml::to_string(self)?;
+            fs::write(path, toml)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn record_update(&mut self) {
+        self.last_update = Some(now());
+    }
+
+    pub fn record_latest_version(&mut self, version: &Version) {
+        self.latest_version = Some(version.clone());
+    }
+
+    /// Cache the latest Veryl version observed on the release server
+    ///
+    /// The cache is optional, so failing to save it is not an error.
+    pub fn cache_latest_version(version: &Version) {
+        let mut state = State::load();
+        state.record_latest_version(version);
+        let _ = state.save();
+    }
+
+    /// Cache the latest verylup version observed on the release server
+    pub fn cache_latest_verylup_version(version: &Version) {
+        let mut state = State::load();
+        state.latest_verylup_version = Some(version.clone());
+        let _ = state.save();
+    }
+
+    pub fn reminder_due(&self, interval: Duration) -> bool {
+        match self.last_reminder {
+            Some(x) => now().saturating_sub(x) >= interval.as_secs(),
+            None => true,
+        }
+    }
+
+    pub fn last_update_elapsed(&self) -> Option<Duration> {
+        let last_update = UNIX_EPOCH + Duration::from_secs(self.last_update?);
+        SystemTime::now().duration_since(last_update).ok()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 60 {
+        return "just now".to_string();
+    }
+
+    let (value, unit) = if secs < 60 * 60 {
+        (secs / 60, "minute")
+    } else if secs < 60 * 60 * 24 {
+        (secs / (60 * 60), "hour")
+    } else {
+        (secs / (60 * 60 * 24), "day")
+    };
+
+    if value == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{value} {unit}s ago")
+    }
+}
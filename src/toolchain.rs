@@ -1,17 +1,122 @@
 use crate::config::Config;
+use crate::state::{now, State};
 use crate::utils::*;
 use anyhow::{anyhow, bail, Error, Result};
-use directories::ProjectDirs;
-use log::info;
+use log::{debug, info, warn};
+use reqwest::Url;
 use semver::Version;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::{Component, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+use std::time::{Instant, UNIX_EPOCH};
+use tempfile::TempDir;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 pub const TOOLS: &[&str] = &["veryl", "veryl-ls"];
 
+/// Tools to be proxied
+///
+/// In addition to `TOOLS`, tools listed in `tools` configuration and executables found in
+/// installed toolchains are included, so tools added to releases are available without
+/// updating verylup. Only executables without extension (or `.exe` on Windows) are discovered,
+/// and they are skipped if a file other than verylup exists next to verylup with the same name,
+/// so such a file is not replaced by a shim.
+pub fn tools() -> Vec<String> {
+    let mut extra = Config::load().tools;
+    let self_path = self_path().ok();
+    let is_free = |name: &str| {
+        let Some(self_path) = &self_path else {
+            return false;
+        };
+        let path = self_path.parent().unwrap().join(exe_name(name));
+        !path.exists() || is_same_file(self_path, &path)
+    };
+    for toolchain in ToolChain::list() {
+        let Ok(entries) = fs::read_dir(toolchain.get_dir()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // files like `install.sh` are not tools, and must not shadow system commands
+            let is_binary = path
+                .extension()
+                .is_none_or(|x| x.eq_ignore_ascii_case("exe"));
+            if !is_binary || !is_executable(&path) {
+                continue;
+            }
+            if let Some(x) = path.file_stem().and_then(|x| x.to_str()) {
+                if is_free(x) {
+                    extra.push(x.to_string());
+                }
+            }
+        }
+    }
+    extra.sort();
+    extra.dedup();
+
+    let mut ret: Vec<_> = TOOLS.iter().map(|x| x.to_string()).collect();
+    ret.extend(
+        extra
+            .into_iter()
+            .filter(|x| x != "verylup" && !TOOLS.contains(&x.as_str())),
+    );
+    ret
+}
+
+/// Sidecar file to record how the toolchain was installed
+const INSTALL_INFO: &str = ".verylup.toml";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstallInfo {
+    /// URL or path where the toolchain was installed from
+    pub source: String,
+
+    /// Unix time of the installation
+    pub installed_at: u64,
+
+    /// Version of the installed toolchain, to refer it without executing
+    #[serde(default)]
+    pub version: Option<Version>,
+}
+
+const TOOLCHAIN_DIR_HINT: &str =
+    "the toolchain directory can be changed by `verylup config set toolchain_dir <path>`";
+
+#[derive(Clone, Debug, Default)]
+pub struct InstallOption {
+    /// Toolchain package path for offline installation
+    pub pkg: Option<PathBuf>,
+
+    /// Make the installed files read-only to share them between users
+    pub system: bool,
+
+    /// Expected SHA256 checksum of the downloaded archive
+    pub sha256: Option<String>,
+
+    /// Install `local` toolchain from the already built binaries
+    pub no_build: bool,
+
+    /// Directory to keep the downloaded archive
+    pub keep_archive: Option<PathBuf>,
+
+    /// Version of the package trusted without executing it
+    pub assume_version: Option<Version>,
+
+    /// Skip signature verification of the downloaded archive
+    pub no_verify_signature: bool,
+
+    /// Check the installed binaries can be executed on this host
+    pub smoke_test: bool,
+
+    /// Skip checksum verification of the downloaded archive
+    pub no_verify: bool,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ToolChain {
     Version(Version),
@@ -21,17 +126,84 @@ pub enum ToolChain {
 
 impl ToolChain {
     pub fn get_actual_version(&self) -> Result<Version> {
-        let path = if cfg!(target_os = "windows") {
-            self.get_path("veryl.exe")
-        } else {
-            self.get_path("veryl")
-        };
+        let path = self.get_path(&exe_name("veryl"));
+        let output = Command::new(&path)
+            .arg("--version")
+            .output()
+            .map_err(|err| anyhow!("failed to execute \"{}\" ({err})", path.to_string_lossy()))?;
 
-        let output = Command::new(path).arg("--version").output()?;
-        let version = String::from_utf8(output.stdout)?;
-        let version = version.strip_prefix("veryl ").unwrap().trim_end();
-        let version = Version::parse(version)?;
-        Ok(version)
+        // the binary may fail to start by missing libraries like glibc
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "\"{}\" failed ({}): {}",
+                path.to_string_lossy(),
+                output.status,
+                stderr.trim()
+            );
+        }
+
+        let version = String::from_utf8_lossy(&output.stdout);
+        let version = version
+            .trim()
+            .strip_prefix("veryl ")
+            .and_then(|x| Version::parse(x).ok());
+        version.ok_or_else(|| {
+            anyhow!(
+                "failed to get the version of \"{}\"",
+                path.to_string_lossy()
+            )
+        })
+    }
+
+    /// Parse a toolchain name given by users, and suggest the closest one if it is unknown
+    pub fn parse(value: &str) -> Result<ToolChain> {
+        ToolChain::try_from(value).map_err(|err| match suggest_toolchain(value) {
+            Some(x) => anyhow!("{err}, did you mean \"{x}\"?"),
+            None => err,
+        })
+    }
+
+    /// Probe actual versions of toolchains concurrently.
+    ///
+    /// The number of concurrent probes is bounded by the available parallelism.
+    pub async fn get_actual_versions(toolchains: &[ToolChain]) -> Vec<Result<Version>> {
+        let limit = std::thread::available_parallelism()
+            .map(|x| x.get())
+            .unwrap_or(4);
+        let semaphore = Arc::new(Semaphore::new(limit));
+
+        let mut set = JoinSet::new();
+        for (i, toolchain) in toolchains.iter().cloned().enumerate() {
+            let semaphore = semaphore.clone();
+            set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let version =
+                    tokio::task::spawn_blocking(move || toolchain.get_actual_version()).await;
+                (i, version.unwrap_or_else(|err| Err(err.into())))
+            });
+        }
+
+        let mut ret: Vec<_> = toolchains
+            .iter()
+            .map(|_| Err(anyhow!("failed to probe the version")))
+            .collect();
+        while let Some(x) = set.join_next().await {
+            if let Ok((i, version)) = x {
+                ret[i] = version;
+            }
+        }
+        ret
+    }
+
+    /// Get the release archive URL of the toolchain
+    ///
+    /// `version` is the resolved version for `latest`.
+    pub fn get_archive_url(&self, version: &Version) -> Result<Url> {
+        match self {
+            ToolChain::Local => Err(local_download_error()),
+            _ => get_archive_url("veryl", version),
+        }
     }
 
     pub fn get_dir(&self) -> PathBuf {
@@ -42,26 +214,46 @@ impl ToolChain {
         self.get_dir().join(bin)
     }
 
-    fn base_dir() -> PathBuf {
-        let project_dir = ProjectDirs::from("org", "veryl-lang", "veryl").unwrap();
-        let data_path = project_dir.data_dir().to_path_buf();
-        data_path.join("toolchains")
+    pub fn base_dir() -> PathBuf {
+        // the system configuration provides the shared directory for all users
+        let toolchain_dir = Config::load()
+            .toolchain_dir
+            .or_else(|| Config::load_system().and_then(|x| x.toolchain_dir));
+        if let Some(x) = toolchain_dir {
+            // relative path is resolved from the binary to keep portable installation relocatable
+            return match portable_dir() {
+                Some(dir) if x.is_relative() => dir.join(x),
+                _ => x,
+            };
+        }
+
+        data_dir().join("toolchains")
     }
 
     pub fn exists(&self) -> bool {
         self.get_dir().exists()
     }
 
+    /// Toolchain overridden for the current project, even if it is not installed
+    pub fn override_toolchain() -> Option<ToolChain> {
+        let config = Config::load();
+        let project = search_project().ok()?;
+        let name = config.overrides.get(&project)?;
+        ToolChain::try_from(name).ok()
+    }
+
     pub fn default_toolchain() -> Option<ToolChain> {
         let config = Config::load();
 
         // directory override
-        let project = search_project();
-        if let Ok(project) = project {
-            if let Some(x) = config.overrides.get(&project) {
-                if let Some(x) = Self::by_name(x) {
-                    return Some(x);
-                }
+        if let Some(x) = Self::override_toolchain().filter(|x| x.exists()) {
+            return Some(x);
+        }
+
+        // temporary default without changing the config
+        if let Ok(x) = std::env::var("VERYLUP_DEFAULT_TOOLCHAIN") {
+            if let Some(x) = Self::by_name(&x) {
+                return Some(x);
             }
         }
 
@@ -72,7 +264,65 @@ impl ToolChain {
             }
         }
 
-        Self::list().last().cloned()
+        // machine-wide default toolchain config
+        if let Some(x) = Config::load_system().and_then(|x| x.default_toolchain) {
+            if let Some(x) = Self::by_name(&x) {
+                return Some(x);
+            }
+        }
+
+        if config.require_explicit_default {
+            return None;
+        }
+
+        Self::fallback_toolchain(&Self::list())
+    }
+
+    /// The newest toolchain of the sorted `list`
+    ///
+    /// Prereleases are used only if no stable toolchain is installed.
+    fn fallback_toolchain(list: &[ToolChain]) -> Option<ToolChain> {
+        list.iter()
+            .rfind(|x| !x.is_prerelease())
+            .or(list.last())
+            .cloned()
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        matches!(self, ToolChain::Version(x) if !x.pre.is_empty())
+    }
+
+    /// Warn if `local` is selected as the fallback default though it is older than `latest`
+    ///
+    /// `local` precedes `latest` by the ordering, so an old `local` silently shadows `latest`.
+    pub fn check_stale_local() {
+        if Config::load().default_toolchain.is_some()
+            || Self::default_toolchain() != Some(ToolChain::Local)
+            || !ToolChain::Latest.exists()
+        {
+            return;
+        }
+
+        let local = ToolChain::Local.get_actual_version();
+        let latest = ToolChain::Latest.get_actual_version();
+        if let (Ok(local), Ok(latest)) = (local, latest) {
+            if local < latest {
+                warn!(
+                    "outdated: \"local\" ({local}) is older than \"latest\" ({latest}) but used as the default toolchain, run `verylup default latest` to prefer \"latest\""
+                );
+            }
+        }
+    }
+
+    /// Same as `default_toolchain`, but returns an error describing why no toolchain is resolved
+    pub fn get_default_toolchain() -> Result<ToolChain> {
+        Self::default_toolchain().ok_or_else(|| {
+            if Config::load().require_explicit_default {
+                anyhow!("no default toolchain set; run `verylup default <spec>`")
+            } else {
+                anyhow!("no toolchain is found")
+            }
+        })
     }
 
     pub fn list() -> Vec<ToolChain> {
@@ -81,7 +331,7 @@ impl ToolChain {
         if let Ok(dirs) = std::fs::read_dir(Self::base_dir()) {
             for dir in dirs.flatten() {
                 let path = dir.path();
-                let name = path.components().last();
+                let name = path.components().next_back();
                 if let Some(Component::Normal(x)) = name {
                     if let Ok(x) = ToolChain::try_from(&x.to_string_lossy().into_owned()) {
                         ret.push(x);
@@ -98,98 +348,442 @@ impl ToolChain {
         let path = Self::base_dir().join(name);
 
         if path.exists() {
-            if let Ok(x) = ToolChain::try_from(name) {
-                Some(x)
-            } else {
-                None
-            }
+            ToolChain::try_from(name).ok()
         } else {
             None
         }
     }
 
-    pub async fn install(&self, pkg: &Option<PathBuf>) -> Result<()> {
-        let file = if let Some(pkg) = pkg {
-            info!("extracting toolchain package: {}", pkg.to_string_lossy());
+    /// Find an installed toolchain whose actual version is `version`
+    ///
+    /// The toolchain named by the version is used if it exists,
+    /// otherwise the actual versions of installed toolchains are probed.
+    pub async fn by_version(version: &Version) -> Option<ToolChain> {
+        let toolchain = ToolChain::Version(version.clone());
+        if toolchain.exists() {
+            return Some(toolchain);
+        }
 
-            let pkg_version = get_package_version(pkg)?;
+        let list = Self::list();
+        let versions = Self::get_actual_versions(&list).await;
+        list.into_iter()
+            .zip(versions)
+            .find(|(_, x)| x.as_ref().ok() == Some(version))
+            .map(|(x, _)| x)
+    }
 
-            if let Ok(actual) = self.get_actual_version() {
-                if pkg_version <= actual {
-                    info!("checking toolchain: {self} (up-to-date)");
-                    return Ok(());
+    /// Resolve to the installed toolchain of the same version if this toolchain is not installed
+    pub async fn resolve_installed(self) -> ToolChain {
+        match &self {
+            ToolChain::Version(x) if !self.exists() => Self::by_version(x).await.unwrap_or(self),
+            _ => self,
+        }
+    }
+
+    pub async fn install(&self, opt: &InstallOption) -> Result<()> {
+        self.check_assume_version(opt)?;
+
+        match &opt.pkg {
+            Some(x) if x.is_dir() => {
+                if opt.assume_version.is_some() {
+                    bail!("\"--assume-version\" can't be used for a directory of packages");
                 }
+                self.install_package_dir(x, opt).await
             }
+            _ => self.install_one(opt).await,
+        }
+    }
 
-            if let ToolChain::Version(x) = self {
-                if *x != pkg_version {
-                    bail!("unexpected package: package version is {pkg_version}");
+    /// Install all packages in `dir` as toolchains of each version
+    ///
+    /// If the toolchain is `latest`, the newest package is installed as `latest` too.
+    /// Files which are not toolchain packages for this host are skipped with a warning.
+    async fn install_package_dir(&self, dir: &Path, opt: &InstallOption) -> Result<()> {
+        ensure_writable(&Self::base_dir(), TOOLCHAIN_DIR_HINT)?;
+
+        // each package is extracted once, and the staging directory is installed as is
+        let mut packages = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && is_zip(&path) {
+                match Self::stage_package(&path, opt) {
+                    Ok((staging, version)) => packages.push((version, path, staging)),
+                    Err(err) => warn!("skipping package: {} ({err:#})", path.to_string_lossy()),
                 }
             }
+        }
+        packages.sort_by(|a, b| a.0.cmp(&b.0));
 
-            File::open(pkg)?
-        } else {
-            let version = match self {
-                ToolChain::Latest => {
-                    let latest = get_latest_version("veryl").await?;
-                    if let Ok(actual) = self.get_actual_version() {
-                        if latest != actual {
-                            Some(latest)
-                        } else {
-                            None
-                        }
-                    } else {
-                        Some(latest)
-                    }
-                }
-                ToolChain::Version(x) => {
-                    if let Ok(actual) = self.get_actual_version() {
-                        if *x != actual {
-                            Some(x.clone())
-                        } else {
-                            None
-                        }
-                    } else {
-                        Some(x.clone())
-                    }
-                }
-                ToolChain::Local => {
-                    local_install()?;
-                    return Ok(());
+        let target = match self {
+            ToolChain::Latest => packages.last(),
+            ToolChain::Version(x) => packages.iter().find(|(y, _, _)| x == y),
+            ToolChain::Local => bail!("local toolchain can't be installed from packages"),
+        };
+        let Some((target_version, target, _)) = target else {
+            bail!(
+                "package for toolchain \"{self}\" is not found in {}",
+                dir.to_string_lossy()
+            );
+        };
+        let (target_version, target) = (target_version.clone(), target.clone());
+
+        // only the failure of the target package is an error
+        for (version, path, staging) in packages {
+            let is_target = version == target_version;
+            let ret =
+                ToolChain::Version(version.clone()).install_staged(staging, version, &path, opt);
+            match ret {
+                Err(err) if !is_target => {
+                    warn!("skipping package: {} ({err:#})", path.to_string_lossy())
                 }
-            };
+                x => x?,
+            }
+        }
 
-            let Some(version) = version else {
-                info!("checking toolchain: {self} (up-to-date)");
-                return Ok(());
+        // `latest` needs another copy of the newest package, but its version is already known
+        if *self == ToolChain::Latest {
+            let opt = InstallOption {
+                pkg: Some(target),
+                assume_version: Some(target_version),
+                ..opt.clone()
             };
+            self.install_one(&opt).await?;
+        }
 
-            info!("downloading toolchain: {self}");
+        Ok(())
+    }
+
+    /// Extract the package into a staging directory, and get the version of it
+    ///
+    /// The staging directory is placed in the same filesystem as the toolchains to be moved by rename.
+    fn stage_package(pkg: &Path, opt: &InstallOption) -> Result<(TempDir, Version)> {
+        let staging = tempfile::tempdir_in(Self::base_dir())?;
+
+        info!("extracting toolchain package: {}", pkg.to_string_lossy());
+
+        let start = Instant::now();
+        unzip(&File::open(pkg)?, staging.path())?;
+        debug!("timing: extraction {:.2?}", start.elapsed());
+
+        let version = if let Some(x) = &opt.assume_version {
+            x.clone()
+        } else {
+            let start = Instant::now();
+            let ret = get_extracted_version(staging.path(), pkg)?;
+            debug!("timing: package version probe {:.2?}", start.elapsed());
+            ret
+        };
+
+        Ok((staging, version))
+    }
+
+    /// Install the package `pkg` of `pkg_version` extracted in `staging` by `stage_package`
+    fn install_staged(
+        &self,
+        staging: TempDir,
+        pkg_version: Version,
+        pkg: &Path,
+        opt: &InstallOption,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let actual = self.get_actual_version().ok();
+        debug!("timing: installed version probe {:.2?}", start.elapsed());
+
+        let up_to_date = actual.as_ref().is_some_and(|x| pkg_version <= *x);
+        debug!(
+            "decision: source: package, reason: --pkg given; action: {}",
+            describe_action(actual.as_ref(), &pkg_version, up_to_date, "extract")
+        );
+        if up_to_date {
+            info!("checking toolchain: {self} (up-to-date)");
+            return Ok(());
+        }
+
+        if let ToolChain::Version(x) = self {
+            if *x != pkg_version {
+                bail!("unexpected package: package version is {pkg_version}");
+            }
+        }
 
-            let url = get_archive_url("veryl", &version)?;
-            let data = download(&url).await?;
-            let mut file = tempfile::tempfile()?;
-            file.write_all(&data)?;
-            file
+        let source = fs::canonicalize(pkg)?.to_string_lossy().into_owned();
+        self.place_staged(staging, &source, Some(&pkg_version), opt)
+    }
+
+    async fn install_one(&self, opt: &InstallOption) -> Result<()> {
+        ensure_writable(&Self::base_dir(), TOOLCHAIN_DIR_HINT)?;
+
+        if let Some(pkg) = &opt.pkg {
+            let (staging, version) = Self::stage_package(pkg, opt)?;
+            return self.install_staged(staging, version, pkg, opt);
+        }
+
+        // archives are extracted into the staging directory at first,
+        // and it is placed in the same filesystem as the toolchains to be moved by rename
+        let staging = tempfile::tempdir_in(Self::base_dir())?;
+
+        let start = Instant::now();
+        let version = match self {
+            ToolChain::Latest => get_latest_toolchain_version().await?,
+            ToolChain::Version(x) => x.clone(),
+            ToolChain::Local => {
+                local_install(opt.no_build, staging.path())?;
+                let source = std::env::current_dir()?;
+                let source = format!("local build ({})", source.to_string_lossy());
+                return self.place_staged(staging, &source, None, opt);
+            }
         };
 
+        let actual = self.get_actual_version().ok();
+        debug!("timing: version resolution {:.2?}", start.elapsed());
+
+        let up_to_date = actual.as_ref() == Some(&version);
+        debug!(
+            "decision: source: {}; action: {}",
+            describe_sources(),
+            describe_action(actual.as_ref(), &version, up_to_date, "download")
+        );
+        if up_to_date {
+            info!("checking toolchain: {self} (up-to-date)");
+            return Ok(());
+        }
+
+        let archives = self.download_archives(&version, opt).await?;
+        self.install_archives(staging, &version, &archives, opt)
+    }
+
+    /// Extract `archives` into `staging`, and replace the toolchain directory by it
+    fn install_archives(
+        &self,
+        staging: TempDir,
+        version: &Version,
+        archives: &[(File, Url)],
+        opt: &InstallOption,
+    ) -> Result<()> {
+        let start = Instant::now();
+        for (file, _) in archives {
+            unzip(file, staging.path())?;
+        }
+        debug!("timing: extraction {:.2?}", start.elapsed());
+
+        let urls: Vec<_> = archives.iter().map(|(_, x)| x.to_string()).collect();
+        self.place_staged(staging, &urls.join(" "), Some(version), opt)
+    }
+
+    /// Replace the toolchain directory by `staging`
+    fn place_staged(
+        &self,
+        staging: TempDir,
+        source: &str,
+        version: Option<&Version>,
+        opt: &InstallOption,
+    ) -> Result<()> {
+        // the existing toolchain is kept if the new one can't be executed
+        if opt.smoke_test {
+            smoke_test(self, staging.path())?;
+        }
+
         info!("installing toolchain: {self}");
 
+        Self::write_install_info(staging.path(), source, version)?;
+        if opt.system {
+            set_readonly_all(staging.path(), true)?;
+        }
+
+        // the existing directory is replaced entirely not to leave files of the previous version
         let dir = self.get_dir();
-        if !dir.exists() {
-            fs::create_dir_all(&dir)?;
+        let trash = tempfile::tempdir_in(Self::base_dir())?;
+        if dir.exists() {
+            set_readonly_all(&dir, false)?;
+            fs::rename(&dir, trash.path().join(self.to_string()))?;
         }
+        if let Err(x) = fs::rename(staging.path(), &dir) {
+            // restore the previous toolchain
+            let _ = fs::rename(trash.path().join(self.to_string()), &dir);
+            return Err(x.into());
+        }
+
+        Ok(())
+    }
+
+    /// The assumed version must match the directory name of the toolchain
+    fn check_assume_version(&self, opt: &InstallOption) -> Result<()> {
+        match (self, &opt.assume_version) {
+            (_, Some(_)) if opt.pkg.is_none() => {
+                bail!("\"--assume-version\" can be used with \"--pkg\" only")
+            }
+            (ToolChain::Local, Some(_)) => {
+                bail!("\"--assume-version\" can't be used for \"local\" toolchain")
+            }
+            (ToolChain::Version(x), Some(y)) if x != y => {
+                bail!("assumed version {y} doesn't match toolchain \"{self}\"")
+            }
+            _ => Ok(()),
+        }
+    }
 
-        unzip(&file, &dir)?;
+    /// Release archives which compose the toolchain
+    ///
+    /// All tools are shipped in a single archive now,
+    /// and archives of tools shipped separately can be added here.
+    fn get_archive_urls(&self, version: &Version) -> Result<Vec<Url>> {
+        Ok(vec![self.get_archive_url(version)?])
+    }
+
+    /// Download the release archives of `version` into temporary files
+    async fn download_archives(
+        &self,
+        version: &Version,
+        opt: &InstallOption,
+    ) -> Result<Vec<(File, Url)>> {
+        info!("downloading toolchain: {self}");
+
+        let urls = self.get_archive_urls(version)?;
+        download_archives(&urls, version, opt).await
+    }
+
+    /// Extract the toolchain into `dir` without registering it as an installed toolchain
+    pub async fn extract_to(&self, dir: &Path, opt: &InstallOption) -> Result<()> {
+        self.check_assume_version(opt)?;
+
+        let files = if let Some(pkg) = &opt.pkg {
+            vec![File::open(pkg)?]
+        } else {
+            let version = match self {
+                ToolChain::Latest => get_latest_toolchain_version().await?,
+                ToolChain::Version(x) => x.clone(),
+                ToolChain::Local => return Err(local_download_error()),
+            };
+            let archives = self.download_archives(&version, opt).await?;
+            archives.into_iter().map(|(x, _)| x).collect()
+        };
+
+        info!("extracting toolchain: {self} -> {}", dir.to_string_lossy());
+        fs::create_dir_all(dir)?;
+        for file in &files {
+            unzip(file, dir)?;
+        }
+
+        if let (Some(pkg), ToolChain::Version(x), None) = (&opt.pkg, self, &opt.assume_version) {
+            let actual = get_extracted_version(dir, pkg)?;
+            if *x != actual {
+                bail!("unexpected package: package version is {actual}");
+            }
+        }
 
         Ok(())
     }
 
+    fn write_install_info(dir: &Path, source: &str, version: Option<&Version>) -> Result<()> {
+        let info = InstallInfo {
+            source: source.to_string(),
+            installed_at: now(),
+            version: version.cloned(),
+        };
+        fs::write(dir.join(INSTALL_INFO), toml::to_string(&info)?)?;
+        Ok(())
+    }
+
+    /// Migrate the toolchain directory created by older verylup, and return whether it is changed
+    pub fn upgrade_data(&self) -> Result<bool> {
+        let path = self.get_dir().join(INSTALL_INFO);
+        if path.exists() {
+            return Ok(false);
+        }
+
+        info!("migrating toolchain: {self} (adding {INSTALL_INFO})");
+
+        // the directory mtime is the best guess of the installation time
+        let installed_at = fs::metadata(self.get_dir())?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        let info = InstallInfo {
+            source: "unknown".to_string(),
+            installed_at,
+            version: None,
+        };
+        self.modify(|_| Ok(fs::write(&path, toml::to_string(&info)?)?))?;
+        Ok(true)
+    }
+
+    pub fn get_install_info(&self) -> Option<InstallInfo> {
+        let toml = fs::read_to_string(self.get_dir().join(INSTALL_INFO)).ok()?;
+        toml::from_str(&toml).ok()
+    }
+
+    /// Get tools which exist in the toolchain directory
+    pub fn get_components(&self) -> Vec<String> {
+        tools()
+            .into_iter()
+            .filter(|x| self.get_path(&exe_name(x)).exists())
+            .collect()
+    }
+
+    pub async fn add_component(&self, tool: &str, pkg: &Option<PathBuf>) -> Result<()> {
+        let version = match self {
+            ToolChain::Version(x) => x.clone(),
+            _ => self.get_actual_version()?,
+        };
+
+        let file = if let Some(pkg) = pkg {
+            // a component of another version must not be mixed into the toolchain
+            let pkg_version = get_package_version(pkg)?;
+            if pkg_version != version {
+                bail!(
+                    "\"{}\" is {pkg_version}, but toolchain \"{self}\" is {version}",
+                    pkg.to_string_lossy()
+                );
+            }
+            File::open(pkg)?
+        } else {
+            info!("downloading toolchain: {self}");
+
+            let url = self.get_archive_url(&version)?;
+            download_archive(url, version, InstallOption::default()).await?
+        };
+
+        info!("adding component: {tool} ({self})");
+
+        self.modify(|dir| {
+            let name = exe_name(tool);
+            if unzip_filter(&file, dir, |x| x == name)? == 0 {
+                bail!("component \"{tool}\" is not found in the package");
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_component(&self, tool: &str) -> Result<()> {
+        let path = self.get_path(&exe_name(tool));
+        if !path.exists() {
+            bail!("component \"{tool}\" is not installed in toolchain \"{self}\"");
+        }
+
+        info!("removing component: {tool} ({self})");
+
+        self.modify(|_| Ok(fs::remove_file(&path)?))
+    }
+
+    /// Modify the toolchain directory keeping whether it is read-only
+    fn modify<F: FnOnce(&Path) -> Result<()>>(&self, f: F) -> Result<()> {
+        let dir = self.get_dir();
+        let readonly = fs::metadata(&dir)?.permissions().readonly();
+
+        set_readonly_all(&dir, false)?;
+        let ret = f(&dir);
+        if readonly {
+            set_readonly_all(&dir, true)?;
+        }
+        ret
+    }
+
     pub fn uninstall(&self) -> Result<()> {
         info!("uninstalling toolchain: {self}");
 
         let dir = self.get_dir();
         if dir.exists() {
+            set_readonly_all(&dir, false)?;
             fs::remove_dir_all(&dir)?;
         } else {
             bail!("toolchain \"{self}\" is not found");
@@ -227,6 +821,29 @@ impl TryFrom<&str> for ToolChain {
     }
 }
 
+/// Find the closest toolchain name from channels and installed toolchains
+///
+/// This reads the toolchain directory, so it should be used only to report an error to users.
+fn suggest_toolchain(value: &str) -> Option<String> {
+    let mut candidates = vec!["latest".to_string(), "local".to_string()];
+
+    if let Ok(dirs) = fs::read_dir(ToolChain::base_dir()) {
+        for dir in dirs.flatten() {
+            let name = dir.file_name().to_string_lossy().into_owned();
+            if Version::parse(&name).is_ok() {
+                candidates.push(name);
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .map(|x| (strsim::levenshtein(value, &x), x))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
 impl TryFrom<&String> for ToolChain {
     type Error = Error;
     fn try_from(value: &String) -> std::result::Result<Self, Self::Error> {
@@ -234,6 +851,10 @@ impl TryFrom<&String> for ToolChain {
     }
 }
 
+/// `local` > `latest` > versions ordered by semver
+///
+/// A prerelease is older than the release of the same version (`0.16.0-rc.1` < `0.16.0`),
+/// and build metadata is compared only if the other parts are equal.
 impl Ord for ToolChain {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         match (self, other) {
@@ -255,7 +876,193 @@ impl PartialOrd for ToolChain {
     }
 }
 
-fn local_install() -> Result<()> {
+/// Resolve the version which `latest` refers
+///
+/// The resolved version is cached for the update reminder even if it is not installed.
+pub async fn get_latest_toolchain_version() -> Result<Version> {
+    let latest = if Config::load().include_prerelease {
+        let releases = get_releases("veryl").await?;
+        releases
+            .iter()
+            .filter_map(|x| parse_tag(&x.tag_name).ok())
+            .max()
+            .ok_or(anyhow!("no release is found"))?
+    } else {
+        get_latest_version("veryl").await?
+    };
+
+    State::cache_latest_version(&latest);
+    Ok(latest)
+}
+
+/// Download and verify an archive into a temporary file
+///
+/// The checksum is taken from `<archive>.sha256`, or the asset digest of the release API if it is
+/// not published. The download fails if neither is available unless verification is disabled
+/// explicitly.
+pub async fn download_archive(url: Url, version: Version, opt: InstallOption) -> Result<File> {
+    let verify = !opt.no_verify && Config::load().verify_downloads;
+    let data = if verify {
+        let sha256 = match &opt.sha256 {
+            Some(x) => x.clone(),
+            None => match get_published_sha256(&url).await {
+                Ok(x) => x,
+                Err(err) => {
+                    debug!("checksum fallback: {err}, trying the release API");
+                    get_asset_sha256(&url, &version).await.map_err(|x| {
+                        anyhow!("{err}, and {x}, \"--no-verify\" or `verylup config set verify_downloads false` skips checksum verification")
+                    })?
+                }
+            },
+        };
+        download_verified(&url, &sha256).await?
+    } else {
+        warn!("skipping checksum verification: {url}, the archive may be corrupted or tampered");
+        download(&url).await?
+    };
+
+    // the archive is verified in memory before anything is written to disk
+    match trusted_key() {
+        Some(_) if opt.no_verify_signature => warn!("skipping signature verification: {url}"),
+        Some(key) => {
+            verify_signature(&url, &data, &key).await?;
+            info!("verified signature: {url}");
+        }
+        None => warn!(
+            "skipping signature verification: {url}, no trusted key is embedded or configured by `trusted_key`"
+        ),
+    }
+
+    if let Some(dir) = &opt.keep_archive {
+        keep_archive(dir, &url, &version, &data)?;
+    }
+
+    let mut file = tempfile::tempfile_in(temp_dir()?)?;
+    file.write_all(&data)?;
+    Ok(file)
+}
+
+/// Download the release archives `urls` concurrently into temporary files
+///
+/// All archives are tried even if some of them fail, and the failures are reported together.
+async fn download_archives(
+    urls: &[Url],
+    version: &Version,
+    opt: &InstallOption,
+) -> Result<Vec<(File, Url)>> {
+    let start = Instant::now();
+
+    let mut set = JoinSet::new();
+    for (i, url) in urls.iter().cloned().enumerate() {
+        let version = version.clone();
+        let mut opt = opt.clone();
+        // the expected checksum is given for the main archive only
+        if i > 0 {
+            opt.sha256 = None;
+        }
+        set.spawn(async move { (i, download_archive(url, version, opt).await) });
+    }
+
+    let mut ret: Vec<_> = (0..urls.len()).map(|_| None).collect();
+    let mut errors = Vec::new();
+    while let Some(x) = set.join_next().await {
+        match x? {
+            (i, Ok(file)) => ret[i] = Some((file, urls[i].clone())),
+            (i, Err(err)) => errors.push(format!("{}: {err:#}", urls[i])),
+        }
+    }
+    debug!("timing: download {:.2?}", start.elapsed());
+
+    if !errors.is_empty() {
+        bail!("failed to download archives\n  {}", errors.join("\n  "));
+    }
+    Ok(ret.into_iter().flatten().collect())
+}
+
+/// Describe whether and why the toolchain is installed, for the decision log
+fn describe_action(
+    installed: Option<&Version>,
+    target: &Version,
+    up_to_date: bool,
+    verb: &str,
+) -> String {
+    match installed {
+        Some(x) if up_to_date => format!("skip because installed {x} is up-to-date with {target}"),
+        Some(x) => format!("{verb} because installed {x} differs from target {target}"),
+        None => format!("{verb} because no version is installed for target {target}"),
+    }
+}
+
+/// Run minimal commands of the toolchain in `dir`
+fn smoke_test(toolchain: &ToolChain, dir: &Path) -> Result<()> {
+    info!("testing toolchain: {toolchain}");
+
+    let commands: &[(&str, &[&str])] = &[
+        ("veryl", &["--version"]),
+        ("veryl", &["--help"]),
+        ("veryl-ls", &["--version"]),
+    ];
+
+    for (tool, args) in commands {
+        let exe = dir.join(exe_name(tool));
+        // components like veryl-ls may be omitted from the package
+        if *tool != "veryl" && !exe.exists() {
+            continue;
+        }
+
+        let command = format!("{tool} {}", args.join(" "));
+        let output = Command::new(&exe).args(*args).output().map_err(|err| {
+            anyhow!(
+                "smoke test failed: `{command}` can't be executed ({err}), the toolchain may not support this host ({})",
+                target()
+            )
+        })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let detail = stderr
+                .lines()
+                .find(|x| !x.trim().is_empty())
+                .unwrap_or_default();
+            bail!(
+                "smoke test failed: `{command}` exited with {}, the toolchain may not support this host ({}): {detail}",
+                output.status,
+                target()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Save the downloaded archive as `<archive>-<version>.zip` in `dir`
+fn keep_archive(dir: &Path, url: &Url, version: &Version, data: &[u8]) -> Result<()> {
+    let name = url
+        .path_segments()
+        .and_then(|mut x| x.next_back())
+        .unwrap_or("veryl");
+    let name = match name.strip_suffix(".zip") {
+        Some(x) => format!("{x}-{version}.zip"),
+        None => format!("{name}-{version}"),
+    };
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(name);
+    fs::write(&path, data)?;
+    info!(
+        "keeping archive: {} (sha256: {})",
+        path.to_string_lossy(),
+        sha256(data)
+    );
+    Ok(())
+}
+
+pub fn local_download_error() -> Error {
+    anyhow!("local toolchain can't be downloaded, it must be rebuilt with `verylup install local`")
+}
+
+/// Build the local toolchain, and copy the binaries into `dir`
+fn local_install(no_build: bool, dir: &Path) -> Result<()> {
     let output = Command::new("cargo")
         .arg("metadata")
         .arg("--no-deps")
@@ -265,57 +1072,339 @@ fn local_install() -> Result<()> {
     let output = String::from_utf8(output.stdout)?;
     let metadata: serde_json::Value = serde_json::from_str(&output)?;
 
-    let temp = tempfile::tempdir()?;
+    let temp = tempfile::tempdir_in(temp_dir()?)?;
     let root = temp.path();
-    let bin = root.join("bin");
-
-    let mut built = false;
-
-    let env_path = if let Some(path) = std::env::var_os("PATH") {
-        let mut paths: Vec<_> = std::env::split_paths(&path).collect();
-        paths.push(bin.clone());
-        std::env::join_paths(paths)?
+    let files = if no_build {
+        let dir = find_built_dir(&metadata)?;
+        TOOLS
+            .iter()
+            .map(|x| dir.join(exe_name(x)))
+            .filter(|x| x.exists())
+            .collect()
     } else {
-        bail!("");
-    };
+        let bin = root.join("bin");
 
-    for pkg in metadata["packages"].as_array().unwrap() {
-        let name = pkg["name"].as_str().unwrap();
-        if TOOLS.contains(&name) {
-            let manifest = PathBuf::from(pkg["manifest_path"].as_str().unwrap());
-            let path = manifest.parent().unwrap();
+        let env_path = if let Some(path) = std::env::var_os("PATH") {
+            let mut paths: Vec<_> = std::env::split_paths(&path).collect();
+            paths.push(bin.clone());
+            std::env::join_paths(paths)?
+        } else {
+            bail!("");
+        };
 
-            info!("building local toolchain: {name}");
+        let packages = tool_packages(&metadata)?;
+        for (path, tools) in &packages {
+            info!("building local toolchain: {}", tools.join(", "));
 
-            let mut child = Command::new("cargo")
-                .arg("install")
-                .arg("--path")
-                .arg(path)
+            let mut cmd = Command::new("cargo");
+            cmd.arg("install").arg("--path").arg(path);
+            for tool in tools {
+                cmd.arg("--bin").arg(tool);
+            }
+            let status = cmd
                 .arg("--root")
                 .arg(root)
                 .env("PATH", &env_path)
-                .spawn()?;
+                .status()?;
+            if !status.success() {
+                bail!(
+                    "failed to build local toolchain: {}",
+                    path.to_string_lossy()
+                );
+            }
+        }
+
+        let mut files = Vec::new();
+        for file in fs::read_dir(bin)? {
+            files.push(file?.path());
+        }
+        files
+    };
+
+    for file in files {
+        let tgt = dir.join(file.file_name().unwrap());
+        fs::copy(file, &tgt)?;
+    }
+
+    Ok(())
+}
+
+/// Find packages which own binary targets of `TOOLS`
+///
+/// Packages are located by binary target names rather than package names,
+/// so other members of the workspace don't matter.
+fn tool_packages(metadata: &serde_json::Value) -> Result<Vec<(PathBuf, Vec<&str>)>> {
+    let mut ret = Vec::new();
+    for pkg in metadata["packages"].as_array().into_iter().flatten() {
+        let tools: Vec<_> = pkg["targets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|x| {
+                x["kind"]
+                    .as_array()
+                    .is_some_and(|x| x.iter().any(|x| x == "bin"))
+            })
+            .filter_map(|x| x["name"].as_str())
+            .filter(|x| TOOLS.contains(x))
+            .collect();
 
-            child.wait()?;
-            built = true;
+        if tools.is_empty() {
+            continue;
+        }
+
+        if let Some(manifest) = pkg["manifest_path"].as_str() {
+            let path = PathBuf::from(manifest).parent().unwrap().to_path_buf();
+            ret.push((path, tools));
         }
     }
 
-    if !built {
+    if ret.is_empty() {
         bail!("this is not Veryl's repository");
     }
+    Ok(ret)
+}
+
+/// Find the target directory which contains the most recently built toolchain
+fn find_built_dir(metadata: &serde_json::Value) -> Result<PathBuf> {
+    tool_packages(metadata)?;
+
+    let target = PathBuf::from(metadata["target_directory"].as_str().unwrap());
+    let veryl = exe_name("veryl");
+
+    ["release", "debug"]
+        .iter()
+        .map(|x| target.join(x))
+        .filter_map(|x| {
+            let modified = fs::metadata(x.join(&veryl)).ok()?.modified().ok()?;
+            Some((modified, x))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, x)| x)
+        .ok_or(anyhow!(
+            "built toolchain is not found in {}, run `cargo build` before",
+            target.to_string_lossy()
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+
+    /// Temporary directory used as `toolchain_dir` and `temp_dir` by all tests
+    fn test_dir() -> &'static Path {
+        static DIR: OnceLock<TempDir> = OnceLock::new();
+        DIR.get_or_init(|| {
+            let dir = tempfile::tempdir().unwrap();
+            let config = Config {
+                toolchain_dir: Some(dir.path().join("toolchains")),
+                temp_dir: Some(dir.path().join("temp")),
+                ..Default::default()
+            };
+            let path = dir.path().join("config.toml");
+            fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+            Config::set_path(path);
+            dir
+        })
+        .path()
+    }
 
-    let dir = ToolChain::Local.get_dir();
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn install_over_polluted_directory() {
+        let base = test_dir();
 
-    if !dir.exists() {
-        fs::create_dir_all(&dir)?;
+        // `latest` of an older version polluted by a file unknown to the package
+        let dir = ToolChain::Latest.get_dir();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("veryl"), "#!/bin/sh\necho veryl 0.1.0\n").unwrap();
+        set_exec(&mut File::open(dir.join("veryl")).unwrap()).unwrap();
+        fs::write(dir.join("stale.txt"), "stale").unwrap();
+
+        let pkg = base.join("latest.zip");
+        write_package(&pkg, "0.2.0", &["new.txt"]);
+        let opt = InstallOption {
+            pkg: Some(pkg),
+            ..Default::default()
+        };
+        ToolChain::Latest.install(&opt).await.unwrap();
+
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, [INSTALL_INFO, "new.txt", "veryl"]);
+        assert_eq!(
+            ToolChain::Latest.get_actual_version().unwrap(),
+            Version::parse("0.2.0").unwrap()
+        );
     }
 
-    for file in fs::read_dir(bin)? {
-        let file = file?;
-        let tgt = dir.join(file.file_name());
-        fs::copy(file.path(), &tgt)?;
+    /// Serve the files of `dir` over HTTP, and respond 404 to unknown paths
+    async fn serve_dir(dir: PathBuf) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let dir = dir.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let n = socket.read(&mut buf).await.unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or_default();
+                    let head = match fs::read(dir.join(path.trim_start_matches('/'))) {
+                        Ok(body) => {
+                            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", body.len());
+                            [head.into_bytes(), b"Connection: close\r\n\r\n".to_vec(), body].concat()
+                        }
+                        Err(_) => {
+                            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                                .to_vec()
+                        }
+                    };
+                    let _ = socket.write_all(&head).await;
+                });
+            }
+        });
+        format!("http://{addr}")
     }
 
-    Ok(())
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn install_multiple_archives() {
+        let base = test_dir();
+        let srv = base.join("srv");
+        fs::create_dir_all(&srv).unwrap();
+        write_package(&srv.join("veryl.zip"), "0.3.0", &[]);
+        write_zip(
+            &srv.join("veryl-ls.zip"),
+            &[("veryl-ls", "#!/bin/sh\necho veryl-ls 0.3.0\n")],
+        );
+
+        let addr = serve_dir(srv).await;
+        let urls: Vec<_> = ["veryl.zip", "veryl-ls.zip"]
+            .iter()
+            .map(|x| Url::parse(&format!("{addr}/{x}")).unwrap())
+            .collect();
+        let version = Version::parse("0.3.0").unwrap();
+        let opt = InstallOption {
+            no_verify: true,
+            no_verify_signature: true,
+            ..Default::default()
+        };
+
+        let toolchain = ToolChain::Version(version.clone());
+        let archives = download_archives(&urls, &version, &opt).await.unwrap();
+        fs::create_dir_all(ToolChain::base_dir()).unwrap();
+        let staging = tempfile::tempdir_in(ToolChain::base_dir()).unwrap();
+        toolchain
+            .install_archives(staging, &version, &archives, &opt)
+            .unwrap();
+
+        let dir = toolchain.get_dir();
+        assert!(dir.join("veryl").exists());
+        assert!(dir.join("veryl-ls").exists());
+        assert_eq!(toolchain.get_actual_version().unwrap(), version);
+
+        // all failures are reported together
+        let urls: Vec<_> = ["missing0.zip", "veryl.zip", "missing1.zip"]
+            .iter()
+            .map(|x| Url::parse(&format!("{addr}/{x}")).unwrap())
+            .collect();
+        let err = download_archives(&urls, &version, &opt).await.unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains(urls[0].as_str()));
+        assert!(!err.contains(urls[1].as_str()));
+        assert!(err.contains(urls[2].as_str()));
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn install_from_mixed_directory() {
+        let base = test_dir();
+        let dir = base.join("mixed");
+        fs::create_dir_all(&dir).unwrap();
+
+        write_package(&dir.join("veryl-x86_64-linux.zip"), "0.4.0", &[]);
+        // a release archive of verylup has no `veryl`
+        write_zip(&dir.join("verylup.zip"), &[("verylup", "verylup")]);
+        // a package for another host can't be executed
+        write_zip(&dir.join("veryl-windows.zip"), &[("veryl.exe", "MZ")]);
+        fs::write(dir.join("README.txt"), "packages").unwrap();
+
+        let version = Version::parse("0.4.0").unwrap();
+        let toolchain = ToolChain::Version(version.clone());
+        let opt = InstallOption {
+            pkg: Some(dir.clone()),
+            ..Default::default()
+        };
+        toolchain.install(&opt).await.unwrap();
+        assert_eq!(toolchain.get_actual_version().unwrap(), version);
+
+        // the target is still required
+        let toolchain = ToolChain::Version(Version::parse("0.4.1").unwrap());
+        assert!(toolchain.install(&opt).await.is_err());
+        assert!(!toolchain.exists());
+    }
+
+    fn toolchains(names: &[&str]) -> Vec<ToolChain> {
+        let mut ret: Vec<_> = names
+            .iter()
+            .map(|x| ToolChain::try_from(*x).unwrap())
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn ord_channels() {
+        let list = toolchains(&["local", "0.16.0", "latest"]);
+        assert_eq!(list, toolchains(&["0.16.0", "latest", "local"]));
+        assert_eq!(list.last(), Some(&ToolChain::Local));
+    }
+
+    #[test]
+    fn ord_prerelease() {
+        let list = toolchains(&["0.16.0", "0.16.0-rc.1", "0.16.0-beta.2", "0.15.0"]);
+        let names: Vec<_> = list.iter().map(|x| x.to_string()).collect();
+        assert_eq!(names, ["0.15.0", "0.16.0-beta.2", "0.16.0-rc.1", "0.16.0"]);
+    }
+
+    #[test]
+    fn ord_build_metadata() {
+        let a = ToolChain::try_from("0.16.0+a").unwrap();
+        let b = ToolChain::try_from("0.16.0+b").unwrap();
+        let c = ToolChain::try_from("0.16.1").unwrap();
+        assert!(a < b);
+        assert!(b < c);
+    }
+
+    #[test]
+    fn fallback_prefers_stable() {
+        let list = toolchains(&["0.15.0", "0.16.0-rc.1"]);
+        assert_eq!(
+            ToolChain::fallback_toolchain(&list),
+            Some(ToolChain::try_from("0.15.0").unwrap())
+        );
+
+        let list = toolchains(&["0.15.0", "0.16.0-rc.1", "latest"]);
+        assert_eq!(
+            ToolChain::fallback_toolchain(&list),
+            Some(ToolChain::Latest)
+        );
+    }
+
+    #[test]
+    fn fallback_to_prerelease() {
+        let list = toolchains(&["0.16.0-beta.1", "0.16.0-rc.1"]);
+        assert_eq!(
+            ToolChain::fallback_toolchain(&list),
+            Some(ToolChain::try_from("0.16.0-rc.1").unwrap())
+        );
+        assert_eq!(ToolChain::fallback_toolchain(&[]), None);
+    }
 }
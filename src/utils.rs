@@ -1,39 +1,512 @@
+use crate::config::Config;
+use crate::toolchain::TOOLS;
 use anyhow::{anyhow, bail, Result};
+use console::Style;
+use fern::Dispatch;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::{debug, info, warn, Level, LevelFilter};
 use reqwest::Url;
 use semver::Version;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use zip::ZipArchive;
 
+/// Marker file next to the verylup binary which enables portable mode
+///
+/// The marker is also used as the configuration file in portable mode.
+pub const PORTABLE_MARKER: &str = "verylup-portable.toml";
+
+/// Directory of the verylup binary if portable mode is enabled
+pub fn portable_dir() -> Option<&'static Path> {
+    static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PORTABLE_DIR
+        .get_or_init(|| {
+            let path = self_path().ok()?;
+            let dir = path.parent()?;
+            dir.join(PORTABLE_MARKER)
+                .is_file()
+                .then(|| dir.to_path_buf())
+        })
+        .as_deref()
+}
+
+/// Directory for temporary files
+///
+/// `temp_dir` configuration is used if set, otherwise the system one which honors `TMPDIR`.
+pub fn temp_dir() -> Result<PathBuf> {
+    match Config::load().temp_dir {
+        Some(x) => {
+            fs::create_dir_all(&x)?;
+            Ok(x)
+        }
+        None => Ok(std::env::temp_dir()),
+    }
+}
+
+pub fn data_dir() -> PathBuf {
+    if let Some(x) = portable_dir() {
+        return x.join("data");
+    }
+
+    let project_dir = directories::ProjectDirs::from("org", "veryl-lang", "veryl").unwrap();
+    project_dir.data_dir().to_path_buf()
+}
+
+const GITHUB_BASE: &str = "https://github.com/veryl-lang";
+const GITHUB_API_BASE: &str = "https://api.github.com/repos/veryl-lang";
+
+/// Base URL of releases, `GITHUB_BASE` unless `release_base_url` is configured
+///
+/// The base URL should have the same layout as `https://github.com/veryl-lang`.
+fn release_base() -> String {
+    match Config::load().release_base() {
+        Some(x) => x.trim_end_matches('/').to_string(),
+        None => GITHUB_BASE.to_string(),
+    }
+}
+
+/// Base URL of the release API, `GITHUB_API_BASE` unless `release_api_url` is configured
+///
+/// The base URL should serve the same responses as `https://api.github.com/repos/veryl-lang`.
+fn release_api_base() -> String {
+    match Config::load().release_api() {
+        Some(x) => x.trim_end_matches('/').to_string(),
+        None => GITHUB_API_BASE.to_string(),
+    }
+}
+
+/// URL under the releases of `project` like `<base>/<project>/releases<path>`
+fn release_url(base: &str, project: &str, path: &str) -> String {
+    format!("{}/{project}/releases{path}", base.trim_end_matches('/'))
+}
+
 pub async fn get_latest_version(project: &str) -> Result<Version> {
-    let url = format!("https://github.com/veryl-lang/{project}/releases/latest");
-    let resp = reqwest::get(url).await?;
-    let path = resp.url().path();
-    let version = path.split("/").last().unwrap();
-    let version = version.strip_prefix('v').unwrap();
-    let version = Version::parse(version)?;
+    let url = Url::parse(&release_url(&release_base(), project, "/latest"))?;
+    with_fallback(&url, |url| async move {
+        let resp = client()?
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|err| request_error(&url, err))?;
+        if !resp.status().is_success() {
+            bail!("failed to get the latest version: {url}");
+        }
+
+        // the URL is redirected to the latest tag like `.../releases/tag/v0.1.0`
+        let path = resp.url().path();
+        if !path.contains("/releases/tag/") {
+            bail!("unexpected redirect of the latest release: {}", resp.url());
+        }
+        let tag = path.rsplit('/').next().unwrap_or_default();
+        parse_tag(tag)
+    })
+    .await
+}
+
+/// Candidates of `url` on the configured mirror and the release base in the order of trial
+///
+/// The mirror should have the same layout as `https://github.com/veryl-lang`.
+fn sources(url: &Url) -> Vec<Url> {
+    let config = Config::load();
+    let base = release_base();
+    let mirrored = config.mirror.as_ref().and_then(|mirror| {
+        let path = url.as_str().strip_prefix(&base)?;
+        Url::parse(&format!("{}{path}", mirror.trim_end_matches('/'))).ok()
+    });
+
+    match mirrored {
+        Some(x) if config.mirror_first => vec![x, url.clone()],
+        Some(x) => vec![url.clone(), x],
+        None => vec![url.clone()],
+    }
+}
+
+/// Describe which source is tried first and why, for the decision log
+pub fn describe_sources() -> String {
+    let config = Config::load();
+    match &config.mirror {
+        Some(_) if config.mirror_first => {
+            "mirror, reason: config.mirror set (GitHub as fallback)".to_string()
+        }
+        Some(_) => "github, reason: config.mirror_first is false (mirror as fallback)".to_string(),
+        None => "github, reason: config.mirror not set".to_string(),
+    }
+}
+
+/// Try `f` for each source of `url` until it succeeds
+async fn with_fallback<T, F, Fut>(url: &Url, f: F) -> Result<T>
+where
+    F: Fn(Url) -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let sources = sources(url);
+    let mut last_err = None;
+    for (i, x) in sources.iter().enumerate() {
+        match f(x.clone()).await {
+            Ok(ret) => {
+                if i > 0 {
+                    info!("fallback succeeded: {x}");
+                }
+                return Ok(ret);
+            }
+            Err(err) => {
+                if sources.len() > 1 {
+                    warn!("failed to access: {x} ({err})");
+                }
+                last_err = Some(err);
+            }
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+pub fn init_logger(level: LevelFilter) -> Result<()> {
+    Dispatch::new()
+        .format(|out, message, record| {
+            let style = match record.level() {
+                Level::Error => Style::new().red().bright(),
+                Level::Warn => Style::new().yellow().bright(),
+                Level::Info => Style::new().green().bright(),
+                Level::Debug => Style::new().cyan().bright(),
+                Level::Trace => Style::new().magenta().bright(),
+            };
+            out.finish(format_args!(
+                "{} {}{}",
+                style.apply_to(format!("[{:<5}]", record.level())),
+                " ".repeat(
+                    12 - format!("{message}")
+                        .split_ascii_whitespace()
+                        .next()
+                        .unwrap()
+                        .len()
+                ),
+                message
+            ))
+        })
+        .level(level)
+        .chain(std::io::stderr())
+        .apply()?;
+    Ok(())
+}
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Confirm all following prompts automatically
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Ask the user to confirm `prompt`
+///
+/// It is confirmed without prompt if `--yes` is specified or stdin is not a terminal.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    use std::io::IsTerminal;
+
+    if ASSUME_YES.load(Ordering::Relaxed) || !io::stdin().is_terminal() {
+        return Ok(true);
+    }
+
+    eprint!("{prompt} [y/N] ");
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+static JSON_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Interval of JSON progress events
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Emit progress events as JSON lines to stdout during downloads and installs
+pub fn set_json_progress(enable: bool) {
+    JSON_PROGRESS.store(enable, Ordering::Relaxed);
+}
+
+fn emit_progress(event: serde_json::Value) {
+    if JSON_PROGRESS.load(Ordering::Relaxed) {
+        let mut stdout = io::stdout().lock();
+        let _ = writeln!(stdout, "{event}");
+        let _ = stdout.flush();
+    }
+}
+
+/// Release metadata provided by GitHub REST API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    pub html_url: String,
+    pub prerelease: bool,
+    pub published_at: Option<String>,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub size: u64,
+    pub browser_download_url: String,
+    /// Digest of the asset like "sha256:..."
+    pub digest: Option<String>,
+}
+
+const USER_AGENT: &str = concat!("verylup/", env!("CARGO_PKG_VERSION"));
+
+/// Maximum number of redirects to follow
+///
+/// Asset downloads are redirected to `objects.githubusercontent.com`,
+/// and proxies may add some hops on the way.
+const MAX_REDIRECTS: usize = 16;
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Convert a timeout error into a message explaining how to change the timeout
+fn request_error(url: &Url, err: reqwest::Error) -> anyhow::Error {
+    if err.is_timeout() {
+        let config = Config::load();
+        anyhow!(
+            "timed out: {url} (connect_timeout: {}s, request_timeout: {}s), they can be changed by `verylup config set`",
+            config.connect_timeout,
+            config.request_timeout
+        )
+    } else {
+        err.into()
+    }
+}
+
+/// Get the HTTP client shared by all requests to reuse connections
+fn client() -> Result<&'static reqwest::Client> {
+    if let Some(x) = CLIENT.get() {
+        return Ok(x);
+    }
+
+    let client = build_client(&Config::load())?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+fn build_client(config: &Config) -> Result<reqwest::Client> {
+    // GitHub REST API rejects requests without User-Agent
+    let user_agent = config.user_agent.as_deref().unwrap_or(USER_AGENT);
+    // User-Agent is a default header of the client, so it is sent to redirected hosts too
+    let mut builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS));
+
+    if config.connect_timeout > 0 {
+        builder = builder.connect_timeout(Duration::from_secs(config.connect_timeout));
+    }
+    if config.request_timeout > 0 {
+        builder = builder.timeout(Duration::from_secs(config.request_timeout));
+    }
+
+    if let Some(path) = &config.ca_bundle {
+        let pem = fs::read(path)?;
+        for cert in reqwest::Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    if let Some(url) = &config.proxy {
+        // reqwest can't establish a tunnel through a Unix domain socket
+        if url.starts_with("unix://") {
+            bail!(
+                "proxy through Unix domain socket is not supported: {url}, \
+                 expose it as a TCP port (e.g. `socat TCP-LISTEN:8080,fork UNIX-CONNECT:<path>`)"
+            );
+        }
+
+        let mut proxy = reqwest::Proxy::all(url)?;
+        if let Some((user, password)) = proxy_credentials(config)? {
+            proxy = proxy.basic_auth(&user, &password);
+        }
+        builder = builder.proxy(proxy);
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            // rustls requires the certificate and the key in a buffer
+            let mut pem = fs::read(cert)?;
+            pem.push(b'\n');
+            pem.extend(fs::read(key)?);
+            builder = builder.identity(reqwest::Identity::from_pem(&pem)?);
+        }
+        (None, None) => (),
+        _ => bail!("both \"client_cert\" and \"client_key\" are required for client certificate"),
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Get the proxy credentials from `proxy_auth_file` or environment variables
+///
+/// They are kept out of the configuration file because it may be readable by others.
+fn proxy_credentials(config: &Config) -> Result<Option<(String, String)>> {
+    if let Some(path) = &config.proxy_auth_file {
+        warn_if_readable_by_others(path);
+        let text = fs::read_to_string(path)?;
+        let line = text.lines().next().unwrap_or("").trim();
+        let Some((user, password)) = line.split_once(':') else {
+            bail!(
+                "\"{}\" should contain the proxy credentials as `user:password`",
+                path.to_string_lossy()
+            );
+        };
+        return Ok(Some((user.to_string(), password.to_string())));
+    }
+
+    match (
+        std::env::var("VERYLUP_PROXY_USER"),
+        std::env::var("VERYLUP_PROXY_PASSWORD"),
+    ) {
+        (Ok(user), Ok(password)) => Ok(Some((user, password))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(not(windows))]
+fn warn_if_readable_by_others(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.permissions().mode() & 0o077 != 0 {
+            warn!(
+                "permission: \"{}\" is accessible by other users, restrict it by `chmod 600`",
+                path.to_string_lossy()
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+fn warn_if_readable_by_others(_path: &Path) {}
+
+pub fn get_release_url(project: &str, version: &Version) -> String {
+    release_url(&release_base(), project, &format!("/tag/v{version}"))
+}
+
+pub async fn get_release(project: &str, version: &Version) -> Result<Release> {
+    let url = release_url(&release_api_base(), project, &format!("/tags/v{version}"));
+    let resp = client()?.get(&url).send().await?;
+
+    if !resp.status().is_success() {
+        bail!("failed to get the release information: {url}");
+    }
+
+    Ok(resp.json().await?)
+}
+
+/// Parse a release tag like `v0.1.0` tolerantly
+///
+/// The `v` prefix is optional, missing minor and patch are filled by zero,
+/// and trailing metadata other than a valid prerelease (e.g. `v0.1.0_hotfix`) is dropped.
+pub fn parse_tag(tag: &str) -> Result<Version> {
+    let x = tag.trim();
+    let x = x.strip_prefix(['v', 'V']).unwrap_or(x);
+    if let Ok(x) = Version::parse(x) {
+        return Ok(x);
+    }
+
+    let end = x
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(x.len());
+    let (core, rest) = x.split_at(end);
+    let numbers: Vec<_> = core
+        .trim_end_matches('.')
+        .split('.')
+        .map(|x| x.parse::<u64>().ok())
+        .collect();
+    let (major, minor, patch) = match numbers[..] {
+        [Some(x)] => (x, 0, 0),
+        [Some(x), Some(y)] => (x, y, 0),
+        [Some(x), Some(y), Some(z)] => (x, y, z),
+        _ => bail!("unsupported release tag: \"{tag}\""),
+    };
+
+    let mut version = Version::new(major, minor, patch);
+    if let Some(x) = rest.strip_prefix('-') {
+        let pre = x.split(|c: char| c == '+' || c.is_whitespace()).next();
+        if let Some(pre) = pre.and_then(|x| semver::Prerelease::new(x).ok()) {
+            version.pre = pre;
+        }
+    }
     Ok(version)
 }
 
+pub async fn get_releases(project: &str) -> Result<Vec<Release>> {
+    let url = release_url(&release_api_base(), project, "?per_page=100");
+    let resp = client()?.get(&url).send().await?;
+
+    if !resp.status().is_success() {
+        bail!("failed to get the release list: {url}");
+    }
+
+    Ok(resp.json().await?)
+}
+
 include!(concat!(env!("OUT_DIR"), "/target.rs"));
+include!(concat!(env!("OUT_DIR"), "/commit.rs"));
 
-pub fn get_archive_url(project: &str, version: &Version) -> Result<Url> {
-    let archive = if TARGET.starts_with("x86_64-unknown-linux") {
+/// Target triple to select release archives
+///
+/// It can be overridden by `VERYLUP_TARGET`.
+/// An x86_64 build running on Apple silicon through Rosetta selects aarch64 archives.
+pub fn target() -> String {
+    if let Ok(x) = std::env::var("VERYLUP_TARGET") {
+        if !x.is_empty() {
+            return x;
+        }
+    }
+
+    if TARGET.starts_with("x86_64-apple") && is_rosetta() {
+        return TARGET.replacen("x86_64", "aarch64", 1);
+    }
+
+    TARGET.to_string()
+}
+
+fn is_rosetta() -> bool {
+    Command::new("sysctl")
+        .args(["-n", "sysctl.proc_translated"])
+        .output()
+        .is_ok_and(|x| String::from_utf8_lossy(&x.stdout).trim() == "1")
+}
+
+pub fn get_archive_name(project: &str) -> Result<String> {
+    get_archive_name_for(project, &target())
+}
+
+pub fn get_archive_name_for(project: &str, target: &str) -> Result<String> {
+    let archive = if target.starts_with("x86_64-unknown-linux") {
         format!("{project}-x86_64-linux.zip")
-    } else if TARGET.starts_with("x86_64-pc-windows") {
+    } else if target.starts_with("x86_64-pc-windows") {
         format!("{project}-x86_64-windows.zip")
-    } else if TARGET.starts_with("x86_64-apple") {
+    } else if target.starts_with("x86_64-apple") {
         format!("{project}-x86_64-mac.zip")
-    } else if TARGET.starts_with("aarch64-apple") {
+    } else if target.starts_with("aarch64-apple") {
         format!("{project}-aarch64-mac.zip")
     } else {
-        bail!("unknown target");
+        bail!("unknown target: {target}");
     };
+    Ok(archive)
+}
+
+pub fn get_archive_url(project: &str, version: &Version) -> Result<Url> {
+    get_archive_url_for(project, version, &target())
+}
 
-    let url =
-        format!("https://github.com/veryl-lang/{project}/releases/download/v{version}/{archive}");
+pub fn get_archive_url_for(project: &str, version: &Version, target: &str) -> Result<Url> {
+    let archive = get_archive_name_for(project, target)?;
+    let path = format!("/download/v{version}/{archive}");
+    let url = release_url(&release_base(), project, &path);
     let url = Url::parse(&url)?;
     Ok(url)
 }
@@ -52,54 +525,951 @@ pub fn set_exec(_file: &mut File) -> Result<()> {
     Ok(())
 }
 
+/// Reset the permission of a temporary file, which is only accessible by the owner, to the usual one
+#[cfg(not(windows))]
+fn set_regular(file: &mut File) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perm = file.metadata()?.permissions();
+    perm.set_mode(0o644);
+    file.set_permissions(perm)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_regular(_file: &mut File) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|x| x.is_file() && x.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("exe"))
+}
+
+#[cfg(not(windows))]
+fn set_readonly(path: &Path, readonly: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perm = fs::metadata(path)?.permissions();
+    let mode = if readonly {
+        perm.mode() & !0o222
+    } else {
+        perm.mode() | 0o200
+    };
+    perm.set_mode(mode);
+    fs::set_permissions(path, perm)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_readonly(path: &Path, readonly: bool) -> Result<()> {
+    let mut perm = fs::metadata(path)?.permissions();
+    perm.set_readonly(readonly);
+    fs::set_permissions(path, perm)?;
+    Ok(())
+}
+
+pub fn set_readonly_all(path: &Path, readonly: bool) -> Result<()> {
+    // directories should be writable before modifying the children
+    if !readonly {
+        set_readonly(path, false)?;
+    }
+
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            set_readonly_all(&entry?.path(), readonly)?;
+        }
+    }
+
+    if readonly {
+        set_readonly(path, true)?;
+    }
+
+    Ok(())
+}
+
+/// Check that files can be created in `dir`, creating it if necessary.
+pub fn ensure_writable(dir: &Path, hint: &str) -> Result<()> {
+    let ret = fs::create_dir_all(dir).and_then(|_| tempfile::tempfile_in(dir).map(|_| ()));
+    match ret {
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            bail!(
+                "\"{}\" is not writable ({err}), {hint}",
+                dir.to_string_lossy()
+            );
+        }
+        x => Ok(x?),
+    }
+}
+
+/// Download `url` through the partial file, falling back to the mirror (or GitHub) if failed
+///
+/// The partial file is kept if the download fails,
+/// and the next download of the same URL resumes from it by a Range request.
 pub async fn download(url: &Url) -> Result<Vec<u8>> {
-    let resp = reqwest::get(url.clone()).await?;
+    let (data, part) = download_partial(url).await?;
+    let _ = fs::remove_file(part);
+    Ok(data)
+}
+
+/// Same as `download`, but keep the partial file for the caller to remove it after verification
+async fn download_partial(url: &Url) -> Result<(Vec<u8>, PathBuf)> {
+    let part = partial_path(url)?;
+    let data = with_fallback(url, |url| {
+        let part = part.clone();
+        async move { download_from(&url, &part).await }
+    })
+    .await?;
+    Ok((data, part))
+}
+
+/// Partial file of `url` keyed by the hash of the URL
+///
+/// It is placed under `temp_dir` if configured, otherwise under the data directory.
+fn partial_path(url: &Url) -> Result<PathBuf> {
+    let dir = match Config::load().temp_dir {
+        Some(_) => temp_dir()?.join("verylup-downloads"),
+        None => data_dir().join("downloads"),
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}.part", sha256(url.as_str().as_bytes()))))
+}
+
+fn file_len(path: &Path) -> u64 {
+    fs::metadata(path).map(|x| x.len()).unwrap_or(0)
+}
+
+/// Download `url` into `part`, and retry interrupted transfers up to `retries` times
+async fn download_from(url: &Url, part: &Path) -> Result<Vec<u8>> {
+    let retries = Config::load().retries;
+    let mut attempt = 0;
+    loop {
+        let before = file_len(part);
+        match download_part(url, part).await {
+            Ok(()) => return Ok(fs::read(part)?),
+            // only transfers which made progress are retried
+            Err(err) if attempt < retries && file_len(part) > before => {
+                attempt += 1;
+                warn!("interrupted: {url} ({err}), resuming ({attempt}/{retries})");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+const ERROR_BODY_LIMIT: usize = 512;
+
+/// Snippet of the response body explaining the failure like an S3 error or a proxy denial page
+async fn error_body(resp: reqwest::Response) -> Option<String> {
+    let body = resp.bytes().await.ok()?;
+    let truncated = body.len() > ERROR_BODY_LIMIT;
+    let body = String::from_utf8_lossy(&body[..body.len().min(ERROR_BODY_LIMIT)]);
+    let body = body.trim();
+    if body.is_empty() {
+        None
+    } else if truncated {
+        Some(format!("{body}..."))
+    } else {
+        Some(body.to_string())
+    }
+}
+
+async fn download_part(url: &Url, part: &Path) -> Result<()> {
+    let mut offset = file_len(part);
+    let mut resp = ranged_get(url, offset).await?;
+
+    // the partial file may be longer than the resource if it was changed on the server
+    if offset > 0 && resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        fs::remove_file(part)?;
+        offset = 0;
+        resp = ranged_get(url, offset).await?;
+    }
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        match error_body(resp).await {
+            Some(body) => bail!("failed to download the archive: {url} ({status})\n{body}"),
+            None => bail!("failed to download the archive: {url} ({status})"),
+        }
+    }
+
+    let content_range = format!("bytes {offset}-");
+    let resumed = offset > 0
+        && resp.status() == reqwest::StatusCode::PARTIAL_CONTENT
+        && resp
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.starts_with(&content_range));
+
+    let mut file = if resumed {
+        info!("resuming download: {offset} bytes already downloaded");
+        fs::OpenOptions::new().append(true).open(part)?
+    } else {
+        if offset > 0 {
+            debug!("resume: not supported by the server, starting over");
+        }
+        offset = 0;
+        File::create(part)?
+    };
+
+    let total = resp.content_length().map(|x| x + offset);
+    let bar = download_bar(total);
+    let mut downloaded = offset;
+    bar.set_position(downloaded);
+    let mut last = Instant::now();
+    loop {
+        let chunk = match resp.chunk().await {
+            Ok(Some(x)) => x,
+            Ok(None) => break,
+            Err(err) => {
+                bar.finish_and_clear();
+                return Err(request_error(url, err));
+            }
+        };
+        file.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+        bar.set_position(downloaded);
+        if last.elapsed() >= PROGRESS_INTERVAL {
+            last = Instant::now();
+            emit_progress(serde_json::json!({
+                "phase": "download",
+                "downloaded": downloaded,
+                "total": total,
+            }));
+        }
+    }
+    emit_progress(serde_json::json!({
+        "phase": "download",
+        "downloaded": downloaded,
+        "total": total,
+    }));
+
+    // the bar is cleared not to be mixed with the following logs
+    bar.finish_and_clear();
+    file.flush()?;
+    Ok(())
+}
+
+/// GET `url` from `offset` bytes
+async fn ranged_get(url: &Url, offset: u64) -> Result<reqwest::Response> {
+    let mut req = client()?.get(url.clone());
+    if offset > 0 {
+        req = req.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    req.send().await.map_err(|err| request_error(url, err))
+}
+
+/// Progress bar of downloads on stderr, or spinner if the size is unknown
+///
+/// It is hidden by `--quiet` and JSON progress events.
+fn download_bar(total: Option<u64>) -> ProgressBar {
+    if log::max_level() < LevelFilter::Info || JSON_PROGRESS.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+
+    // concurrent downloads show their own bars without overwriting each other
+    static BARS: OnceLock<MultiProgress> = OnceLock::new();
+    let bars = BARS.get_or_init(MultiProgress::new);
+
+    match total {
+        Some(x) => {
+            let style = ProgressStyle::with_template(
+                "{bar:40} {bytes}/{total_bytes} ({percent}%, eta {eta})",
+            )
+            .unwrap();
+            bars.add(ProgressBar::new(x).with_style(style))
+        }
+        None => {
+            let style = ProgressStyle::with_template("{spinner} {bytes}").unwrap();
+            let bar = bars.add(ProgressBar::new_spinner().with_style(style));
+            bar.enable_steady_tick(Duration::from_millis(100));
+            bar
+        }
+    }
+}
+
+/// Get the checksum published as `<archive>.sha256` next to the archive
+pub async fn get_published_sha256(url: &Url) -> Result<String> {
+    let sha_url = Url::parse(&format!("{url}.sha256"))?;
+    with_fallback(&sha_url, |url| async move {
+        let resp = client()?
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|err| request_error(&url, err))?;
+        if !resp.status().is_success() {
+            bail!("checksum is not published: {url} ({})", resp.status());
+        }
+        let text = resp.text().await?;
+
+        // the format of `sha256sum` like `<hash>  <file name>`
+        let hash = text.split_whitespace().next().unwrap_or_default();
+        let valid = hash.len() == 64 && hash.chars().all(|x| x.is_ascii_hexdigit());
+        if !valid {
+            bail!("invalid checksum: {url}");
+        }
+        Ok(hash.to_ascii_lowercase())
+    })
+    .await
+}
+
+/// Get the checksum of the release archive `url` from the asset digest of the release API
+pub async fn get_asset_sha256(url: &Url, version: &Version) -> Result<String> {
+    // the URL is like `<base>/<project>/releases/download/<tag>/<name>`
+    let segments: Vec<_> = url.path_segments().map(|x| x.collect()).unwrap_or_default();
+    let project = segments
+        .iter()
+        .rposition(|x| *x == "releases")
+        .and_then(|x| x.checked_sub(1))
+        .map(|x| segments[x]);
+    let (Some(project), Some(name)) = (project, segments.last()) else {
+        bail!("unexpected release URL: {url}");
+    };
+
+    let release = get_release(project, version).await?;
+    release
+        .assets
+        .iter()
+        .find(|x| x.name == *name)
+        .and_then(|x| x.digest.as_deref()?.strip_prefix("sha256:"))
+        .map(|x| x.to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("digest is not found in the release information: {name}"))
+}
+
+/// Minisign public key of release archives embedded at build time by `VERYLUP_TRUSTED_KEY`
+const EMBEDDED_KEY: Option<&str> = option_env!("VERYLUP_TRUSTED_KEY");
+
+/// Public key to verify signatures, `trusted_key` configuration precedes the embedded one
+pub fn trusted_key() -> Option<String> {
+    Config::load()
+        .trusted_key
+        .or(EMBEDDED_KEY.map(String::from))
+        .filter(|x| !x.trim().is_empty())
+}
+
+/// Verify `data` by the detached signature published as `<archive>.minisig`
+pub async fn verify_signature(url: &Url, data: &[u8], key: &str) -> Result<()> {
+    let key = minisign_verify::PublicKey::from_base64(key.trim())
+        .map_err(|err| anyhow!("invalid trusted key ({err})"))?;
+
+    let sig_url = Url::parse(&format!("{url}.minisig"))?;
+    let text = with_fallback(&sig_url, |url| async move {
+        let resp = client()?.get(url.clone()).send().await?;
+        if !resp.status().is_success() {
+            bail!("failed to get the signature: {url}");
+        }
+        Ok(resp.text().await?)
+    })
+    .await?;
+
+    let signature = minisign_verify::Signature::decode(&text)
+        .map_err(|err| anyhow!("invalid signature: {sig_url} ({err})"))?;
+    key.verify(data, &signature, false)
+        .map_err(|err| anyhow!("signature verification failed: {url} ({err})"))?;
+    Ok(())
+}
+
+/// Get the size of `url` by HEAD request, `None` if the server doesn't report it
+pub async fn get_content_length(url: &Url) -> Result<Option<u64>> {
+    let resp = client()?.head(url.clone()).send().await?;
 
     if !resp.status().is_success() {
-        bail!("failed to download the archive: {url}");
+        bail!("failed to get the archive information: {url}");
     }
 
-    Ok(resp.bytes().await?.to_vec())
+    // `Response::content_length` is always 0 for HEAD, so the header is referred directly
+    let length = resp
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse().ok());
+    Ok(length)
+}
+
+pub fn sha256(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Download and check that the SHA256 checksum matches `expected`
+///
+/// The archive is re-downloaded up to `retries` times on mismatch to recover from transient corruption.
+pub async fn download_verified(url: &Url, expected: &str) -> Result<Vec<u8>> {
+    let retries = Config::load().retries;
+    let mut attempt = 0;
+    loop {
+        let (data, part) = download_partial(url).await?;
+        let ret = check_sha256(url, &data, expected);
+
+        // the corrupted partial file is removed too, so the retry starts over
+        let _ = fs::remove_file(part);
+        let err = match ret {
+            Ok(()) => return Ok(data),
+            Err(err) => err,
+        };
+
+        if attempt >= retries {
+            return Err(err);
+        }
+        attempt += 1;
+        warn!("{err}, re-downloading ({attempt}/{retries})");
+    }
+}
+
+/// Check that the SHA256 checksum of `data` downloaded from `url` matches `expected`
+pub fn check_sha256(url: &Url, data: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256(data);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("checksum mismatch: {url} (expected: {expected}, actual: {actual})");
+    }
+    Ok(())
 }
 
 pub fn unzip(file: &File, dir: &Path) -> Result<()> {
+    unzip_filter(file, dir, |_| true)?;
+    Ok(())
+}
+
+/// Extract entries which match `filter`, and return the number of extracted entries
+///
+/// If some entries are resolved to the same path, the first one is extracted and the others are skipped.
+///
+/// Each entry is written to a temporary file and renamed into place, so a partially written file
+/// never appears in `dir`. If extraction fails, the entries extracted so far are removed.
+pub fn unzip_filter<F: Fn(&str) -> bool>(file: &File, dir: &Path, filter: F) -> Result<usize> {
+    let mut created = Vec::new();
+    let ret = unzip_entries(file, dir, filter, &mut created);
+    if ret.is_err() {
+        for x in &created {
+            let _ = fs::remove_file(x);
+        }
+    }
+    ret
+}
+
+fn unzip_entries<F: Fn(&str) -> bool>(
+    file: &File,
+    dir: &Path,
+    filter: F,
+    created: &mut Vec<PathBuf>,
+) -> Result<usize> {
     let mut zip = ZipArchive::new(file)?;
+    let total = zip.len();
+    let mut count = 0;
+    let mut extracted = HashSet::new();
     for i in 0..zip.len() {
         let mut src = zip.by_index(i)?;
-        let path = dir.join(src.name());
-        let mut tgt = File::create(&path)?;
+        if !filter(src.name()) {
+            continue;
+        }
+
+        let Some(name) = src.enclosed_name() else {
+            warn!("skipping invalid entry: {}", src.name());
+            continue;
+        };
+        let name: PathBuf = name
+            .components()
+            .filter(|x| matches!(x, Component::Normal(_)))
+            .collect();
+        if !extracted.insert(name.clone()) {
+            warn!("skipping duplicated entry: {}", src.name());
+            continue;
+        }
+
+        let path = dir.join(name);
+        let mut temp = tempfile::NamedTempFile::new_in(path.parent().unwrap_or(dir))?;
+        let tgt = temp.as_file_mut();
         let mut buf = Vec::new();
         src.read_to_end(&mut buf)?;
         tgt.write_all(&buf)?;
-        set_exec(&mut tgt)?;
+
+        // only tools and entries marked as executable in the archive get exec bit
+        let is_tool = path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| TOOLS.contains(&x));
+        let is_exec = src.unix_mode().is_some_and(|x| x & 0o111 != 0);
+        if is_tool || is_exec {
+            set_exec(tgt)?;
+        } else {
+            set_regular(tgt)?;
+        }
+        if let Some(mtime) = src.last_modified().and_then(|x| zip_time_to_unix(&x)) {
+            let mtime = filetime::FileTime::from_unix_time(mtime, 0);
+            filetime::set_file_handle_times(tgt, None, Some(mtime))?;
+        }
+        temp.persist(&path).map_err(|x| x.error)?;
+        created.push(path);
+        count += 1;
+        emit_progress(serde_json::json!({
+            "phase": "extract",
+            "extracted": i + 1,
+            "total": total,
+        }));
+    }
+    Ok(count)
+}
+
+/// Convert the timestamp of a zip entry to Unix time
+///
+/// The timestamp has no timezone, so it is treated as UTC.
+fn zip_time_to_unix(time: &zip::DateTime) -> Option<i64> {
+    if !time.is_valid() {
+        return None;
+    }
+
+    // days from civil date
+    let (y, m, d) = (time.year() as i64, time.month() as i64, time.day() as i64);
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = time.hour() as i64 * 3600 + time.minute() as i64 * 60 + time.second() as i64;
+    Some(days * 86400 + secs)
+}
+
+/// Total size of files under `path` in bytes
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+
+    if metadata.is_dir() {
+        fs::read_dir(path)
+            .map(|x| x.flatten().map(|x| dir_size(&x.path())).sum())
+            .unwrap_or(0)
+    } else {
+        metadata.len()
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for x in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = x;
+    }
+    format!("{value:.1} {unit}")
+}
+
+pub fn exe_name(name: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Locate the running verylup binary
+///
+/// On Linux, `current_exe()` may return a path with " (deleted)" suffix if the binary was replaced,
+/// or a path under `/proc` in some container environments.
+/// In these cases, the path invoked by `argv[0]` and the default path of `cargo install` are tried,
+/// and they are used only if they are verified to be the running binary.
+pub fn self_path() -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let path = match exe.to_str().and_then(|x| x.strip_suffix(" (deleted)")) {
+        Some(x) => PathBuf::from(x),
+        None => exe.clone(),
+    };
+
+    if !path.starts_with("/proc") {
+        if let Ok(x) = path.canonicalize() {
+            return Ok(x);
+        }
+    }
+
+    // the link of `/proc/self/exe` is followed even if it can't be resolved as a path
+    let running = if cfg!(target_os = "linux") {
+        PathBuf::from("/proc/self/exe")
+    } else {
+        exe
+    };
+    for candidate in self_path_candidates() {
+        if is_same_file(&candidate, &running) {
+            if let Ok(x) = candidate.canonicalize() {
+                return Ok(x);
+            }
+        }
+    }
+
+    bail!(
+        "failed to locate verylup binary (\"{}\"), run verylup by its full path",
+        path.to_string_lossy()
+    );
+}
+
+/// Known paths where the running verylup may be placed
+fn self_path_candidates() -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+
+    // `argv[0]` without separator was searched in PATH by the shell,
+    // and it may be a link of proxied tools placed next to verylup
+    if let Some(arg0) = std::env::args_os().next().map(PathBuf::from) {
+        let verylup = exe_name("verylup");
+        if arg0.components().count() > 1 {
+            ret.push(arg0.with_file_name(&verylup));
+        } else if let Some(paths) = std::env::var_os("PATH") {
+            ret.extend(
+                std::env::split_paths(&paths)
+                    .filter(|x| x.join(&arg0).is_file())
+                    .map(|x| x.join(&verylup)),
+            );
+        }
+    }
+
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| directories::BaseDirs::new().map(|x| x.home_dir().join(".cargo")));
+    if let Some(x) = cargo_home {
+        ret.push(x.join("bin").join(exe_name("verylup")));
+    }
+
+    ret
+}
+
+/// Whether `a` and `b` refer the same file including hardlinks
+#[cfg(not(windows))]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `a` and `b` refer the same file including hardlinks
+#[cfg(windows)]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    // file index is not available from std on stable, so it is got by the handle
+    fn file_id(path: &Path) -> Option<(u32, u32, u32)> {
+        let file = File::open(path).ok()?;
+        let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+        let ret = unsafe { GetFileInformationByHandle(file.as_raw_handle() as _, &mut info) };
+        (ret != 0).then_some((
+            info.dwVolumeSerialNumber,
+            info.nFileIndexHigh,
+            info.nFileIndexLow,
+        ))
+    }
+
+    match (file_id(a), file_id(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
     }
-    Ok(())
 }
 
 pub fn search_project() -> Result<PathBuf> {
     let dir = std::env::current_dir()?;
     for p in dir.ancestors() {
-        if p.join("Veryl.toml").exists() {
-            return Ok(dir);
+        let path = p.join("Veryl.toml");
+        match fs::metadata(&path) {
+            Ok(_) => {
+                if let Err(err) = File::open(&path) {
+                    warn!("unreadable project: {} ({err})", path.to_string_lossy());
+                }
+                return Ok(dir);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => {
+                warn!(
+                    "inaccessible project: {} ({err}), directory override may not be applied",
+                    path.to_string_lossy()
+                );
+            }
         }
     }
     Err(anyhow!("Veryl project is not found"))
 }
 
+/// Whether `path` is a zip archive regardless of the file name
+pub fn is_zip(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut x| x.read_exact(&mut magic))
+        .is_ok_and(|_| magic == *b"PK\x03\x04")
+}
+
+/// File to pin the verylup version in a project
+const VERYLUP_VERSION_FILE: &str = ".verylup-version";
+
+/// Get the verylup version pinned by `.verylup-version` in the current project
+pub fn pinned_verylup_version() -> Option<Version> {
+    let dir = std::env::current_dir().ok()?;
+    for p in dir.ancestors() {
+        let path = p.join(VERYLUP_VERSION_FILE);
+        if let Ok(text) = fs::read_to_string(&path) {
+            let version = Version::parse(text.trim());
+            if version.is_err() {
+                warn!("ignoring invalid version: {}", path.to_string_lossy());
+            }
+            return version.ok();
+        }
+
+        // don't search beyond the project root
+        if p.join("Veryl.toml").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Warn if the running verylup differs from the version pinned by the project
+pub fn check_pinned_verylup_version() {
+    if let Some(x) = pinned_verylup_version() {
+        let version = env!("CARGO_PKG_VERSION");
+        if x.to_string() != version {
+            warn!("mismatched verylup: {version} is running, but the project requires {x}");
+        }
+    }
+}
+
+/// Get the toolchain version by executing `veryl --version` in the package
+///
+/// The file name of the package is not referred, so it can be renamed freely.
 pub fn get_package_version(path: &Path) -> Result<Version> {
-    let temp = tempfile::tempdir()?;
+    let temp = tempfile::tempdir_in(temp_dir()?)?;
     let file = File::open(path)?;
     unzip(&file, temp.path())?;
+    get_extracted_version(temp.path(), path)
+}
 
-    let path = if cfg!(target_os = "windows") {
-        temp.path().join("veryl.exe")
-    } else {
-        temp.path().join("veryl")
-    };
+/// Get the toolchain version from the package `path` extracted in `dir`
+pub fn get_extracted_version(dir: &Path, path: &Path) -> Result<Version> {
+    let exe = dir.join(exe_name("veryl"));
+    if !exe.exists() {
+        bail!(
+            "\"{}\" is not a toolchain package for this platform ({})",
+            path.to_string_lossy(),
+            target()
+        );
+    }
 
-    let output = Command::new(path).arg("--version").output()?;
-    let version = String::from_utf8(output.stdout)?;
-    let version = version.strip_prefix("veryl ").unwrap().trim_end();
-    let version = Version::parse(version)?;
-    Ok(version)
+    let output = Command::new(exe).arg("--version").output().map_err(|err| {
+        anyhow!(
+            "failed to execute veryl in \"{}\" ({err}), it may be a package for another platform",
+            path.to_string_lossy()
+        )
+    })?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    let version = version
+        .trim()
+        .strip_prefix("veryl ")
+        .and_then(|x| Version::parse(x).ok());
+    version.ok_or_else(|| {
+        anyhow!(
+            "failed to get the version of \"{}\"",
+            path.to_string_lossy()
+        )
+    })
+}
+
+/// Write a zip archive of `files` given as `(name, contents)` for tests
+#[cfg(test)]
+pub fn write_zip(path: &Path, files: &[(&str, &str)]) {
+    let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+    let opt = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+    for (name, contents) in files {
+        zip.start_file(*name, opt).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+/// Write a package whose `veryl` prints `version`, with additional `files` for tests
+#[cfg(test)]
+pub fn write_package(path: &Path, version: &str, files: &[&str]) {
+    let veryl = format!("#!/bin/sh\necho veryl {version}\n");
+    let mut entries = vec![("veryl", veryl.as_str())];
+    entries.extend(files.iter().map(|x| (*x, *x)));
+    write_zip(path, &entries);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn release_url_default() {
+        assert_eq!(
+            release_url(GITHUB_BASE, "veryl", "/latest"),
+            "https://github.com/veryl-lang/veryl/releases/latest"
+        );
+        assert_eq!(
+            release_url(GITHUB_API_BASE, "veryl", "?per_page=100"),
+            "https://api.github.com/repos/veryl-lang/veryl/releases?per_page=100"
+        );
+    }
+
+    #[test]
+    fn release_url_custom_base() {
+        assert_eq!(
+            release_url(
+                "https://mirror.example.com/veryl-lang/",
+                "veryl",
+                "/download/v0.16.0/veryl-x86_64-linux.zip"
+            ),
+            "https://mirror.example.com/veryl-lang/veryl/releases/download/v0.16.0/veryl-x86_64-linux.zip"
+        );
+        assert_eq!(
+            release_url("http://127.0.0.1:8080/api", "verylup", "/tags/v0.1.0"),
+            "http://127.0.0.1:8080/api/verylup/releases/tags/v0.1.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn request_timeout() {
+        // the server accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let config = Config {
+            request_timeout: 1,
+            ..Default::default()
+        };
+        let client = build_client(&config).unwrap();
+        let url = Url::parse(&format!("http://{addr}/veryl.zip")).unwrap();
+
+        let start = Instant::now();
+        let err = client.get(url.clone()).send().await.unwrap_err();
+        assert!(err.is_timeout());
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let err = request_error(&url, err);
+        assert!(err.to_string().starts_with("timed out:"));
+    }
+
+    #[test]
+    fn is_zip_regardless_of_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["weird", "veryl-build-0.18.0-20260101.pkg", "PACKAGE.ZIP"] {
+            let path = dir.path().join(name);
+            write_package(&path, "0.18.0", &[]);
+            assert!(is_zip(&path), "{name}");
+        }
+
+        let path = dir.path().join("notes.zip");
+        fs::write(&path, "not a zip").unwrap();
+        assert!(!is_zip(&path));
+        assert!(!is_zip(&dir.path().join("missing.zip")));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn extracted_version_of_odd_names() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, version) in [
+            ("weird", "0.18.1"),
+            ("veryl-build-0.18.0-20260101.pkg", "0.18.2"),
+        ] {
+            let path = dir.path().join(name);
+            write_package(&path, version, &[]);
+
+            let extracted = tempfile::tempdir().unwrap();
+            unzip(&File::open(&path).unwrap(), extracted.path()).unwrap();
+            let actual = get_extracted_version(extracted.path(), &path).unwrap();
+            assert_eq!(actual, Version::parse(version).unwrap());
+        }
+    }
+
+    #[test]
+    fn extracted_version_without_veryl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.pkg");
+        let err = get_extracted_version(dir.path(), &path).unwrap_err();
+        assert!(err.to_string().contains("is not a toolchain package"));
+    }
+
+    #[test]
+    fn check_sha256_matches() {
+        let url = Url::parse("https://example.com/veryl.zip").unwrap();
+        assert!(check_sha256(&url, b"abc", ABC_SHA256).is_ok());
+        assert!(check_sha256(&url, b"abc", &ABC_SHA256.to_ascii_uppercase()).is_ok());
+    }
+
+    #[test]
+    fn check_sha256_mismatch() {
+        let url = Url::parse("https://example.com/veryl.zip").unwrap();
+        let err = check_sha256(&url, b"abd", ABC_SHA256).unwrap_err();
+        assert!(err.to_string().starts_with("checksum mismatch"));
+        assert!(check_sha256(&url, b"", ABC_SHA256).is_err());
+    }
+
+    fn tag(x: &str) -> String {
+        parse_tag(x).unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_tag_prefix() {
+        assert_eq!(tag("v0.16.0"), "0.16.0");
+        assert_eq!(tag("V0.16.0"), "0.16.0");
+        assert_eq!(tag("0.16.0"), "0.16.0");
+    }
+
+    #[test]
+    fn parse_tag_whitespace() {
+        assert_eq!(tag("  v0.16.0\n"), "0.16.0");
+        assert_eq!(tag("\t0.16.0 "), "0.16.0");
+    }
+
+    #[test]
+    fn parse_tag_suffix() {
+        assert_eq!(tag("v0.16.0-rc.1"), "0.16.0-rc.1");
+        assert_eq!(tag("v0.16.0+build.5"), "0.16.0+build.5");
+        assert_eq!(tag("v0.16.0-beta.2+build.5"), "0.16.0-beta.2+build.5");
+        assert_eq!(tag("v0.16-rc.1"), "0.16.0-rc.1");
+        assert_eq!(tag("v0.16.0_hotfix"), "0.16.0");
+    }
+
+    #[test]
+    fn parse_tag_partial() {
+        assert_eq!(tag("v0.16"), "0.16.0");
+        assert_eq!(tag("v1"), "1.0.0");
+    }
+
+    #[test]
+    fn parse_tag_invalid() {
+        assert!(parse_tag("").is_err());
+        assert!(parse_tag("nightly").is_err());
+        assert!(parse_tag("v0.16.0.1").is_err());
+    }
 }